@@ -0,0 +1,556 @@
+use anyhow::{anyhow, bail, Context, Result};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use strum::IntoEnumIterator;
+
+use crate::enums::{EtlStage, SystemType};
+use crate::py_utils::py_path_to_string;
+use crate::stages::{CustomStage, Stage, StageArg};
+use crate::systems::{CustomSystem, System, SystemArg};
+
+/// Alias tables accepted in a config file or dict
+const TABLES: &str = "'stages', 'systems', 'custom_stages' or 'custom_systems'";
+
+/// Key an alias is stored and looked up under, so that padding and case never matter
+fn alias_key(alias: &str) -> String {
+    alias.trim().to_lowercase()
+}
+
+/// A custom stage as written in a config file: `{"ordinal": 25, "aliases": [...]}`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomStageSpec {
+    ordinal: i64,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+/// A custom system as written in a config file: `{"aliases": [...], "attributes": {...}}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomSystemSpec {
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    attributes: BTreeMap<String, String>,
+}
+
+/// Stage and system aliases added at runtime on top of the built-in tables, and the
+/// custom stages and systems they may point to.
+///
+/// Aliases are matched case-insensitively and take precedence over the built-in ones,
+/// so a team can also re-point an existing alias. Sorted maps keep the `Debug` output,
+/// which the index cache key is built from, stable.
+#[pyclass]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AliasRegistry {
+    stages: BTreeMap<String, Stage>,
+    systems: BTreeMap<String, System>,
+    custom_stages: BTreeMap<String, CustomStage>,
+    custom_systems: BTreeMap<String, CustomSystem>,
+}
+
+impl AliasRegistry {
+    pub fn add_stage(&mut self, alias: &str, stage: Stage) {
+        self.stages.insert(alias_key(alias), stage);
+    }
+
+    pub fn add_system(&mut self, alias: &str, system: System) {
+        self.systems.insert(alias_key(alias), system);
+    }
+
+    /// Register a custom stage under its name and aliases
+    pub fn register_stage(&mut self, custom: CustomStage) {
+        for alias in custom.all_aliases() {
+            self.add_stage(alias, Stage::Custom(custom.clone()));
+        }
+        self.custom_stages.insert(alias_key(custom.name()), custom);
+    }
+
+    /// Register a custom system under its name and aliases
+    pub fn register_system(&mut self, custom: CustomSystem) {
+        for alias in custom.all_aliases() {
+            self.add_system(alias, System::Custom(custom.clone()));
+        }
+        self.custom_systems.insert(alias_key(custom.name()), custom);
+    }
+
+    /// Stage for a name or alias, user-defined aliases and custom stages first
+    pub fn stage(&self, alias: &str) -> Option<Stage> {
+        let alias = alias_key(alias);
+        self.stages
+            .get(&alias)
+            .cloned()
+            .or_else(|| EtlStage::from_alias(&alias).ok().map(Stage::Builtin))
+    }
+
+    /// System for a name or alias, user-defined aliases and custom systems first
+    pub fn system(&self, alias: &str) -> Option<System> {
+        self.folder_system(alias).or_else(|| {
+            SystemType::from_alias(&alias_key(alias))
+                .ok()
+                .map(System::Builtin)
+        })
+    }
+
+    /// System a folder stands for: a user-defined or built-in alias. Unlike `system`,
+    /// a folder named after a built-in type without aliases (`other`) is no system.
+    pub fn folder_system(&self, folder: &str) -> Option<System> {
+        let folder = alias_key(folder);
+        self.systems.get(&folder).cloned().or_else(|| {
+            SystemType::iter()
+                .find(|system_type| system_type.aliases().contains(&folder.as_str()))
+                .map(System::Builtin)
+        })
+    }
+
+    // Stage named by `target` in a `stages` table: a registered custom stage or a
+    // built-in name or alias
+    fn resolve_stage(&self, target: &str) -> Result<Stage> {
+        let target = alias_key(target);
+        if let Some(custom) = self.custom_stages.get(&target) {
+            return Ok(Stage::Custom(custom.clone()));
+        }
+        EtlStage::from_alias(&target)
+            .map(Stage::Builtin)
+            .map_err(|e| anyhow!(e))
+    }
+
+    // System named by `target` in a `systems` table: a registered custom system or a
+    // built-in name or alias
+    fn resolve_system(&self, target: &str) -> Result<System> {
+        let target = alias_key(target);
+        if let Some(custom) = self.custom_systems.get(&target) {
+            return Ok(System::Custom(custom.clone()));
+        }
+        SystemType::from_alias(&target)
+            .map(System::Builtin)
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Registry from a JSON config file such as
+    /// `{"stages": {"10_stg": "extract"}, "systems": {"gp": "postgres"},
+    ///   "custom_stages": {"validate": {"ordinal": 25, "aliases": ["qa"]}},
+    ///   "custom_systems": {"trino": {"aliases": ["tr"], "attributes": {...}}}}`,
+    /// where targets are names or built-in aliases
+    pub fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Cannot read {path}"))?;
+        let parse = || -> Result<Self> {
+            let mut tables: BTreeMap<String, serde_json::Value> = serde_json::from_str(&text)?;
+            let mut registry = AliasRegistry::default();
+            // custom stages and systems go first so the other tables can point to them
+            if let Some(custom) = tables.remove("custom_stages") {
+                let custom: BTreeMap<String, CustomStageSpec> = serde_json::from_value(custom)?;
+                for (name, spec) in custom {
+                    registry.register_stage(CustomStage::new(&name, spec.ordinal, spec.aliases));
+                }
+            }
+            if let Some(custom) = tables.remove("custom_systems") {
+                let custom: BTreeMap<String, CustomSystemSpec> = serde_json::from_value(custom)?;
+                for (name, spec) in custom {
+                    registry.register_system(CustomSystem::new(
+                        &name,
+                        spec.aliases,
+                        spec.attributes,
+                    ));
+                }
+            }
+            for (table, aliases) in tables {
+                let aliases: BTreeMap<String, String> = serde_json::from_value(aliases)?;
+                for (alias, target) in aliases {
+                    registry.add(&table, &alias, &target)?;
+                }
+            }
+            Ok(registry)
+        };
+        parse().with_context(|| format!("Invalid alias file {path}"))
+    }
+
+    // Add one entry of the `stages` or `systems` table, `target` naming a known value
+    fn add(&mut self, table: &str, alias: &str, target: &str) -> Result<()> {
+        match table {
+            "stages" => {
+                let stage = self.resolve_stage(target)?;
+                self.add_stage(alias, stage)
+            }
+            "systems" => {
+                let system = self.resolve_system(target)?;
+                self.add_system(alias, system)
+            }
+            other => bail!("Unknown alias table '{other}'; expected {TABLES}"),
+        }
+        Ok(())
+    }
+
+    // Custom stages given from Python: a list of CustomStage or a dict shaped like the
+    // `custom_stages` table of the config file
+    fn register_stages_py(&mut self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        if let Ok(list) = value.downcast::<PyList>() {
+            for custom in list.iter() {
+                self.register_stage(custom.extract()?);
+            }
+            return Ok(());
+        }
+        let specs = value.downcast::<PyDict>().map_err(|_| {
+            PyValueError::new_err("custom_stages must be a list of CustomStage or a dict")
+        })?;
+        for (name, spec) in specs.iter() {
+            let name: String = name.extract()?;
+            let spec = spec.downcast::<PyDict>().map_err(|_| {
+                PyValueError::new_err(format!("Custom stage '{name}' must be a dict"))
+            })?;
+            let mut ordinal = None;
+            let mut aliases = Vec::new();
+            for (key, value) in spec.iter() {
+                match key.extract::<String>()?.as_str() {
+                    "ordinal" => ordinal = Some(value.extract()?),
+                    "aliases" => aliases = value.extract()?,
+                    other => {
+                        return Err(PyValueError::new_err(format!(
+                            "Unknown key '{other}' for custom stage '{name}'"
+                        )))
+                    }
+                }
+            }
+            let ordinal = ordinal.ok_or_else(|| {
+                PyValueError::new_err(format!("Custom stage '{name}' needs an ordinal"))
+            })?;
+            self.register_stage(CustomStage::new(&name, ordinal, aliases));
+        }
+        Ok(())
+    }
+
+    // Custom systems given from Python: a list of CustomSystem or a dict shaped like
+    // the `custom_systems` table of the config file
+    fn register_systems_py(&mut self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        if let Ok(list) = value.downcast::<PyList>() {
+            for custom in list.iter() {
+                self.register_system(custom.extract()?);
+            }
+            return Ok(());
+        }
+        let specs = value.downcast::<PyDict>().map_err(|_| {
+            PyValueError::new_err("custom_systems must be a list of CustomSystem or a dict")
+        })?;
+        for (name, spec) in specs.iter() {
+            let name: String = name.extract()?;
+            let spec = spec.downcast::<PyDict>().map_err(|_| {
+                PyValueError::new_err(format!("Custom system '{name}' must be a dict"))
+            })?;
+            let mut custom = CustomSystemSpec::default();
+            for (key, value) in spec.iter() {
+                match key.extract::<String>()?.as_str() {
+                    "aliases" => custom.aliases = value.extract()?,
+                    "attributes" => custom.attributes = value.extract()?,
+                    other => {
+                        return Err(PyValueError::new_err(format!(
+                            "Unknown key '{other}' for custom system '{name}'"
+                        )))
+                    }
+                }
+            }
+            self.register_system(CustomSystem::new(&name, custom.aliases, custom.attributes));
+        }
+        Ok(())
+    }
+
+    /// Registry for an `aliases` argument: an AliasRegistry, a dict shaped like the
+    /// config file (targets may also be enum members or custom stages and systems) or
+    /// the path of a config file
+    pub fn from_py(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(registry) = value.downcast::<AliasRegistry>() {
+            return Ok(registry.borrow().clone());
+        }
+        let Ok(tables) = value.downcast::<PyDict>() else {
+            let path = py_path_to_string("aliases", value)?;
+            return Self::load(&path).map_err(|e| PyValueError::new_err(format!("{e:#}")));
+        };
+        let mut registry = AliasRegistry::default();
+        if let Some(custom) = tables.get_item("custom_stages")? {
+            registry.register_stages_py(&custom)?;
+        }
+        if let Some(custom) = tables.get_item("custom_systems")? {
+            registry.register_systems_py(&custom)?;
+        }
+        for (table, aliases) in tables.iter() {
+            let table: String = table.extract()?;
+            if table == "custom_stages" || table == "custom_systems" {
+                continue;
+            }
+            let aliases = aliases.downcast::<PyDict>().map_err(|_| {
+                PyValueError::new_err(format!("Alias table '{table}' must be a dict"))
+            })?;
+            for (alias, target) in aliases.iter() {
+                let alias: String = alias.extract()?;
+                match table.as_str() {
+                    "stages" => {
+                        let stage = registry.resolve_stage_arg(target.extract()?)?;
+                        registry.add_stage(&alias, stage);
+                    }
+                    "systems" => {
+                        let system = registry.resolve_system_arg(target.extract()?)?;
+                        registry.add_system(&alias, system);
+                    }
+                    other => {
+                        return Err(PyValueError::new_err(format!(
+                            "Unknown alias table '{other}'; expected {TABLES}"
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Stage for an `EtlStage`, `CustomStage` or name given from Python
+    pub fn resolve_stage_arg(&self, stage: StageArg) -> PyResult<Stage> {
+        match stage {
+            StageArg::Builtin(stage) => Ok(Stage::Builtin(stage)),
+            StageArg::Custom(custom) => Ok(Stage::Custom(custom)),
+            StageArg::Name(name) => self
+                .resolve_stage(&name)
+                .map_err(|e| PyValueError::new_err(e.to_string())),
+        }
+    }
+
+    /// System for a `SystemType`, `CustomSystem` or name given from Python
+    pub fn resolve_system_arg(&self, system: SystemArg) -> PyResult<System> {
+        match system {
+            SystemArg::Builtin(system_type) => Ok(System::Builtin(system_type)),
+            SystemArg::Custom(custom) => Ok(System::Custom(custom)),
+            SystemArg::Name(name) => self
+                .resolve_system(&name)
+                .map_err(|e| PyValueError::new_err(e.to_string())),
+        }
+    }
+}
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl AliasRegistry {
+        /// `stages` / `systems` map aliases to `EtlStage` / `SystemType` members, custom
+        /// stages and systems or their names; `custom_stages` and `custom_systems` are
+        /// registered under their own aliases
+        #[new]
+        #[pyo3(signature = (stages=None, systems=None, custom_stages=None, custom_systems=None))]
+        fn py_new(
+            py: Python<'_>,
+            stages: Option<Bound<'_, PyDict>>,
+            systems: Option<Bound<'_, PyDict>>,
+            custom_stages: Option<Bound<'_, PyAny>>,
+            custom_systems: Option<Bound<'_, PyAny>>,
+        ) -> PyResult<Self> {
+            let tables = PyDict::new_bound(py);
+            if let Some(stages) = stages {
+                tables.set_item("stages", stages)?;
+            }
+            if let Some(systems) = systems {
+                tables.set_item("systems", systems)?;
+            }
+            if let Some(custom_stages) = custom_stages {
+                tables.set_item("custom_stages", custom_stages)?;
+            }
+            if let Some(custom_systems) = custom_systems {
+                tables.set_item("custom_systems", custom_systems)?;
+            }
+            Self::from_py(tables.as_any())
+        }
+
+        /// Load a JSON alias file: `{"stages": {alias: name}, "systems": {alias: name},
+        /// "custom_stages": {name: {"ordinal": 25, "aliases": [...]}},
+        /// "custom_systems": {name: {"aliases": [...], "attributes": {...}}}}`
+        #[staticmethod]
+        fn from_file(path: &Bound<'_, PyAny>) -> PyResult<Self> {
+            let path = py_path_to_string("path", path)?;
+            Self::load(&path).map_err(|e| PyValueError::new_err(format!("{e:#}")))
+        }
+
+        /// Map `alias` to an `EtlStage`, a `CustomStage` or the name of either
+        #[pyo3(name = "add_stage")]
+        fn add_stage_py(&mut self, alias: &str, stage: StageArg) -> PyResult<()> {
+            let stage = self.resolve_stage_arg(stage)?;
+            self.add_stage(alias, stage);
+            Ok(())
+        }
+
+        /// Map `alias` to a `SystemType`, a `CustomSystem` or the name of either
+        #[pyo3(name = "add_system")]
+        fn add_system_py(&mut self, alias: &str, system: SystemArg) -> PyResult<()> {
+            let system = self.resolve_system_arg(system)?;
+            self.add_system(alias, system);
+            Ok(())
+        }
+
+        #[pyo3(name = "register_stage")]
+        fn register_stage_py(&mut self, stage: CustomStage) {
+            self.register_stage(stage)
+        }
+
+        #[pyo3(name = "register_system")]
+        fn register_system_py(&mut self, system: CustomSystem) {
+            self.register_system(system)
+        }
+
+        /// Stage for a name or alias: an `EtlStage`, a `CustomStage` or None
+        #[pyo3(name = "stage")]
+        fn stage_py(&self, alias: &str) -> Option<Stage> {
+            self.stage(alias)
+        }
+
+        /// System for a name or alias: a `SystemType`, a `CustomSystem` or None
+        #[pyo3(name = "system")]
+        fn system_py(&self, alias: &str) -> Option<System> {
+            self.system(alias)
+        }
+
+        /// User-defined stage aliases, custom stages' own aliases included
+        #[getter]
+        fn stages(&self) -> BTreeMap<String, Stage> {
+            self.stages.clone()
+        }
+
+        /// User-defined system aliases, custom systems' own aliases included
+        #[getter]
+        fn systems(&self) -> BTreeMap<String, System> {
+            self.systems.clone()
+        }
+
+        /// Registered custom stages, in pipeline order
+        #[getter(custom_stages)]
+        fn custom_stages_py(&self) -> Vec<CustomStage> {
+            let mut stages: Vec<CustomStage> = self.custom_stages.values().cloned().collect();
+            stages.sort_by_key(|stage| stage.ordinal());
+            stages
+        }
+
+        /// Registered custom systems
+        #[getter(custom_systems)]
+        fn custom_systems_py(&self) -> Vec<CustomSystem> {
+            self.custom_systems.values().cloned().collect()
+        }
+
+        fn __repr__(&self) -> String {
+            format!(
+                "AliasRegistry(stages={}, systems={}, custom_stages={}, custom_systems={})",
+                self.stages.len(),
+                self.systems.len(),
+                self.custom_stages.len(),
+                self.custom_systems.len()
+            )
+        }
+    }
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin(system_type: SystemType) -> Option<System> {
+        Some(System::Builtin(system_type))
+    }
+
+    fn stage(stage: EtlStage) -> Option<Stage> {
+        Some(Stage::Builtin(stage))
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let mut registry = AliasRegistry::default();
+        registry.add_stage("10_STG", Stage::Builtin(EtlStage::Extract));
+        registry.add_system("gp", System::Builtin(SystemType::PostgreSQL));
+        registry.add_system("pg", System::Builtin(SystemType::Other));
+
+        assert_eq!(registry.stage("10_stg"), stage(EtlStage::Extract));
+        assert_eq!(registry.stage("02_transform"), stage(EtlStage::Transform));
+        assert_eq!(registry.stage("20_dm"), None);
+        assert_eq!(
+            registry.folder_system("GP"),
+            builtin(SystemType::PostgreSQL)
+        );
+        // user aliases win over built-in ones
+        assert_eq!(registry.folder_system("pg"), builtin(SystemType::Other));
+        assert_eq!(registry.folder_system("duck"), builtin(SystemType::Duckdb));
+        assert_eq!(registry.folder_system("other"), None);
+        assert_eq!(registry.system("other"), builtin(SystemType::Other));
+    }
+
+    #[test]
+    fn test_padded_aliases() {
+        let mut registry = AliasRegistry::default();
+        registry.add_stage(" Raw ", Stage::Builtin(EtlStage::Extract));
+        registry.add_system(" GP\t", System::Builtin(SystemType::PostgreSQL));
+
+        for alias in ["raw", " RAW ", "raw\n"] {
+            assert_eq!(registry.stage(alias), stage(EtlStage::Extract));
+        }
+        assert_eq!(registry.stage(" load "), stage(EtlStage::Load));
+        assert_eq!(registry.system(" gp "), builtin(SystemType::PostgreSQL));
+        assert_eq!(
+            registry.folder_system("gp"),
+            builtin(SystemType::PostgreSQL)
+        );
+        assert_eq!(registry.system(" other "), builtin(SystemType::Other));
+    }
+
+    #[test]
+    fn test_custom_stages() {
+        let mut registry = AliasRegistry::default();
+        let validate = CustomStage::new("Validate", 25, vec!["25_validate".into()]);
+        registry.register_stage(validate.clone());
+        registry.add("stages", "qa", "validate").unwrap();
+
+        for alias in ["validate", "25_VALIDATE", "qa"] {
+            assert_eq!(registry.stage(alias), Some(Stage::Custom(validate.clone())));
+        }
+        assert_eq!(registry.stage("load"), stage(EtlStage::Load));
+        assert!(registry.add("stages", "x", "publish").is_err());
+    }
+
+    #[test]
+    fn test_custom_systems() {
+        let mut registry = AliasRegistry::default();
+        let trino = CustomSystem::new("Trino", vec!["tr".into()], BTreeMap::new());
+        registry.register_system(trino.clone());
+        registry.add("systems", "presto", "trino").unwrap();
+
+        for alias in ["trino", "TR", "presto"] {
+            assert_eq!(
+                registry.folder_system(alias),
+                Some(System::Custom(trino.clone()))
+            );
+        }
+        assert_eq!(registry.system("trino").unwrap().name(), "Trino");
+        assert!(registry.add("systems", "x", "snowflake").is_err());
+    }
+
+    #[test]
+    fn test_registry_file() {
+        let path =
+            std::env::temp_dir().join(format!("subtask-aliases-{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(
+            &path,
+            r#"{"stages": {"20_dm": "load", "qa": "publish"}, "systems": {"gp": "greenplum"},
+                "custom_stages": {"publish": {"ordinal": 35}},
+                "custom_systems": {"greenplum": {"attributes": {"dialect": "postgres"}}}}"#,
+        )
+        .unwrap();
+        let registry = AliasRegistry::load(&path).unwrap();
+        assert_eq!(registry.stage("20_dm"), stage(EtlStage::Load));
+        assert_eq!(registry.stage("qa").unwrap().ordinal(), 35);
+        assert_eq!(registry.folder_system("gp").unwrap().name(), "greenplum");
+
+        fs::write(&path, r#"{"stages": {"20_dm": "nope"}}"#).unwrap();
+        assert!(AliasRegistry::load(&path).is_err());
+        fs::write(&path, r#"{"tasks": {"x": "sql"}}"#).unwrap();
+        assert!(AliasRegistry::load(&path).is_err());
+        fs::write(&path, r#"{"custom_stages": {"publish": {}}}"#).unwrap();
+        assert!(AliasRegistry::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
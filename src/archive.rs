@@ -0,0 +1,401 @@
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::change_detection::FileFingerprint;
+use crate::virtual_tree::{entry_fingerprint, entry_name, IGNORE_FILENAMES};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    fn for_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `path` is an existing file with a supported archive extension
+pub fn is_archive(path: &str) -> bool {
+    let path = Path::new(path);
+    path.is_file() && ArchiveFormat::for_path(path).is_some()
+}
+
+/// Which tar entries are read while the archive is streamed; the rest are read
+/// again from the archive when asked for. Zip entries are always read on demand.
+///
+/// A `.tar` is read at the recorded position of the entry, but a `.tar.gz` cannot be
+/// seeked and is streamed again up to it, so reading many entries this one leaves out
+/// costs time quadratic in the size of the archive.
+#[derive(Clone, Debug, Default)]
+pub struct Preload {
+    extensions: HashSet<String>,
+    max_size: Option<u64>,
+}
+
+impl Preload {
+    /// Preload task files with one of `extensions` (lowercase, without the dot) and
+    /// ignore files, unless they are larger than `max_size` bytes
+    pub fn new(extensions: &HashSet<String>, max_size: Option<u64>) -> Self {
+        Preload {
+            extensions: extensions.clone(),
+            max_size,
+        }
+    }
+
+    fn wants(&self, name: &str, size: u64) -> bool {
+        if self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        let path = Path::new(name);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        IGNORE_FILENAMES.contains(&file_name.as_ref())
+            || path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| self.extensions.contains(&ext.to_lowercase()))
+    }
+}
+
+#[derive(Debug)]
+struct ArchiveEntry {
+    size: u64,
+    modified: Option<SystemTime>,
+    // content read while opening; `None` when it is read on demand
+    data: Option<Vec<u8>>,
+    // position among the members of the archive; with duplicate names the last wins
+    index: usize,
+    // where the body of an uncompressed tar entry starts in the file
+    offset: Option<u64>,
+    // checksum of a zip entry
+    crc32: Option<u32>,
+}
+
+/// A `.zip`, `.tar` or `.tar.gz` task bundle.
+///
+/// Entries are keyed by their archive-relative path with `/` separators. Entries
+/// whose names would escape the archive (absolute or `..` paths) are left out and
+/// listed in `rejected`. Zip entries are read when asked for; tar entries the
+/// `Preload` wants are read while the archive is streamed, others on demand.
+#[derive(Debug)]
+pub struct Archive {
+    path: String,
+    format: ArchiveFormat,
+    entries: BTreeMap<String, ArchiveEntry>,
+    rejected: Vec<String>,
+    zip: Option<Mutex<zip::ZipArchive<File>>>,
+}
+
+impl Archive {
+    pub fn open(path: &str, preload: &Preload) -> Result<Self> {
+        let format = match ArchiveFormat::for_path(Path::new(path)) {
+            Some(format) => format,
+            None => bail!("{path} is not a .zip, .tar or .tar.gz archive"),
+        };
+        let mut archive = Archive {
+            path: path.to_string(),
+            format,
+            entries: BTreeMap::new(),
+            rejected: Vec::new(),
+            zip: None,
+        };
+        let file = archive.open_file()?;
+        match format {
+            ArchiveFormat::Zip => archive.read_zip(file),
+            ArchiveFormat::Tar => archive.read_tar(file, preload),
+            ArchiveFormat::TarGz => archive.read_tar(GzDecoder::new(file), preload),
+        }
+        .with_context(|| format!("Cannot read archive {path}"))?;
+        Ok(archive)
+    }
+
+    fn open_file(&self) -> Result<File> {
+        File::open(&self.path).with_context(|| format!("Cannot open archive {}", self.path))
+    }
+
+    // only the central directory is read; entry bodies are read by `read`
+    fn read_zip(&mut self, file: File) -> Result<()> {
+        let mut zip = zip::ZipArchive::new(file)?;
+        for i in 0..zip.len() {
+            let entry = zip.by_index_raw(i)?;
+            if !entry.is_file() {
+                continue;
+            }
+            let Some(name) = entry.enclosed_name().and_then(|p| entry_name(&p)) else {
+                self.rejected.push(entry.name().to_string());
+                continue;
+            };
+            self.entries.insert(
+                name,
+                ArchiveEntry {
+                    size: entry.size(),
+                    modified: None,
+                    data: None,
+                    index: i,
+                    offset: None,
+                    crc32: Some(entry.crc32()),
+                },
+            );
+        }
+        self.zip = Some(Mutex::new(zip));
+        Ok(())
+    }
+
+    fn read_tar<R: Read>(&mut self, reader: R, preload: &Preload) -> Result<()> {
+        let mut tar = tar::Archive::new(reader);
+        for (index, entry) in tar.entries()?.enumerate() {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let raw_path = entry.path()?.into_owned();
+            let Some(name) = entry_name(&raw_path) else {
+                self.rejected.push(raw_path.to_string_lossy().to_string());
+                continue;
+            };
+            let size = entry.size();
+            let modified = entry
+                .header()
+                .mtime()
+                .ok()
+                .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+            let data = match preload.wants(&name, size) {
+                true => Some(read_body(&mut entry, size)?),
+                false => None,
+            };
+            let entry = ArchiveEntry {
+                size,
+                modified,
+                data,
+                index,
+                offset: (self.format == ArchiveFormat::Tar).then(|| entry.raw_file_position()),
+                crc32: None,
+            };
+            self.entries.insert(name, entry);
+        }
+        Ok(())
+    }
+
+    // stream the tar archive again up to the member at `index`
+    fn read_tar_entry<R: Read>(&self, reader: R, name: &str, index: usize) -> Result<Vec<u8>> {
+        let mut tar = tar::Archive::new(reader);
+        let Some(entry) = tar.entries()?.nth(index) else {
+            return Err(self.not_found(name));
+        };
+        let mut entry = entry?;
+        let size = entry.size();
+        read_body(&mut entry, size)
+    }
+
+    fn not_found(&self, name: &str) -> anyhow::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{name} not found in {}", self.path),
+        )
+        .into()
+    }
+
+    /// Path of the archive file itself
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Archive-relative paths of every file entry, sorted
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|k| k.as_str())
+    }
+
+    /// Entry names that were skipped because they point outside the archive
+    pub fn rejected(&self) -> &[String] {
+        &self.rejected
+    }
+
+    /// Raw bytes of the entry at `name`
+    pub fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let Some(entry) = self.entries.get(name) else {
+            return Err(self.not_found(name));
+        };
+        if let Some(data) = &entry.data {
+            return Ok(data.clone());
+        }
+        if let Some(zip) = &self.zip {
+            let mut zip = zip.lock().unwrap();
+            let mut file = zip.by_index(entry.index)?;
+            return read_body(&mut file, entry.size);
+        }
+        let mut file = self.open_file()?;
+        if let Some(offset) = entry.offset {
+            file.seek(SeekFrom::Start(offset))?;
+            return read_body(&mut file, entry.size);
+        }
+        match self.format {
+            ArchiveFormat::TarGz => self.read_tar_entry(GzDecoder::new(file), name, entry.index),
+            _ => self.read_tar_entry(file, name, entry.index),
+        }
+    }
+
+    /// Size of the entry at `name` as its header declares it, known without reading it
+    pub fn size(&self, name: &str) -> Option<u64> {
+        self.entries.get(name).map(|entry| entry.size)
+    }
+
+    pub fn fingerprint(&self, name: &str) -> Option<FileFingerprint> {
+        let entry = self.entries.get(name)?;
+        Some(match &entry.data {
+            Some(data) => entry_fingerprint(data, entry.modified),
+            None => FileFingerprint::new(entry.size, entry.modified, entry.crc32.map(u64::from)),
+        })
+    }
+}
+
+/// Body of an archive entry, never more than the `size` its header declares
+fn read_body(entry: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    entry.take(size).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_for_path() {
+        assert_eq!(
+            ArchiveFormat::for_path(Path::new("tasks.ZIP")),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::for_path(Path::new("tasks.tar.gz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::for_path(Path::new("tasks.tgz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::for_path(Path::new("tasks.tar")),
+            Some(ArchiveFormat::Tar)
+        );
+        assert_eq!(ArchiveFormat::for_path(Path::new("tasks.sql")), None);
+    }
+
+    fn temp_archive(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("subtask-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().to_string()
+    }
+
+    fn sql_only() -> Preload {
+        Preload::new(&HashSet::from(["sql".to_string()]), Some(64))
+    }
+
+    #[test]
+    fn test_tar_reads_other_entries_on_demand() {
+        let path = temp_archive("tasks.tar");
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        let big = "x".repeat(100);
+        let entries = [
+            ("a/x.sql", "SELECT 1;"),
+            ("a/blob.bin", "binary"),
+            ("a/big.sql", big.as_str()),
+            (".ignore", "*.tmp"),
+        ];
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+
+        let archive = Archive::open(&path, &sql_only()).unwrap();
+        assert!(archive.entries["a/x.sql"].data.is_some());
+        assert!(archive.entries[".ignore"].data.is_some());
+        assert!(archive.entries["a/blob.bin"].data.is_none());
+        // over the size limit: known by its header, never read up front
+        assert!(archive.entries["a/big.sql"].data.is_none());
+        assert_eq!(archive.size("a/big.sql"), Some(100));
+        assert_eq!(archive.read("a/blob.bin").unwrap(), b"binary");
+        assert_eq!(archive.read("a/x.sql").unwrap(), b"SELECT 1;");
+        assert!(archive.read("a/missing.sql").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tar_duplicate_names_read_last_member() {
+        for name in ["dup.tar", "dup.tar.gz"] {
+            let path = temp_archive(name);
+            let file = File::create(&path).unwrap();
+            let writer: Box<dyn std::io::Write> = match name.ends_with(".gz") {
+                true => Box::new(flate2::write::GzEncoder::new(
+                    file,
+                    flate2::Compression::default(),
+                )),
+                false => Box::new(file),
+            };
+            let mut builder = tar::Builder::new(writer);
+            let entries = [
+                ("a/x.sql", "SELECT 1;"),
+                ("a/x.bin", "first"),
+                ("a/x.sql", "SELECT 22;"),
+                ("a/x.bin", "second!"),
+            ];
+            for (name, data) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_cksum();
+                builder
+                    .append_data(&mut header, name, data.as_bytes())
+                    .unwrap();
+            }
+            builder.into_inner().unwrap().flush().unwrap();
+
+            let archive = Archive::open(&path, &sql_only()).unwrap();
+            assert_eq!(archive.files().collect::<Vec<_>>(), ["a/x.bin", "a/x.sql"]);
+            assert_eq!(archive.read("a/x.sql").unwrap(), b"SELECT 22;");
+            // read on demand, the member described by `size` and `fingerprint`
+            assert!(archive.entries["a/x.bin"].data.is_none());
+            assert_eq!(archive.size("a/x.bin"), Some(7));
+            assert_eq!(archive.read("a/x.bin").unwrap(), b"second!");
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_zip_reads_entries_on_demand() {
+        let path = temp_archive("tasks.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("a/x.sql", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, b"SELECT 1;").unwrap();
+        zip.finish().unwrap();
+
+        let archive = Archive::open(&path, &sql_only()).unwrap();
+        assert!(archive.entries["a/x.sql"].data.is_none());
+        assert_eq!(archive.read("a/x.sql").unwrap(), b"SELECT 1;");
+        let fingerprint = archive.fingerprint("a/x.sql").unwrap();
+        assert_eq!(fingerprint.len(), 9);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::time::SystemTime;
+
+use crate::decoding::TextDecoder;
+use crate::file_loader::read_text;
+use crate::models::Subtask;
+use crate::task_source::TaskSource;
+
+/// Cheap per-file state used to spot edits without reading the file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileFingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+    // content hash, for sources where reading is as cheap as a stat
+    hash: Option<u64>,
+}
+
+impl FileFingerprint {
+    pub fn new(len: u64, modified: Option<SystemTime>, hash: Option<u64>) -> Self {
+        FileFingerprint {
+            len,
+            modified,
+            hash,
+        }
+    }
+
+    /// Size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Last modification time, when the source records one
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Stat `path`; `None` when the file cannot be inspected, which always counts as changed
+    pub fn of(path: &str) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(FileFingerprint::new(meta.len(), meta.modified().ok(), None))
+    }
+}
+
+/// Whether the file behind `subtask` now holds different text in `source` than the one
+/// already read. Unread subtasks have nothing to compare against and always count as changed.
+pub fn content_changed(subtask: &Subtask, source: &dyn TaskSource, decoder: &TextDecoder) -> bool {
+    if !subtask.is_loaded() {
+        return true;
+    }
+    match (
+        subtask.load_command(),
+        read_text(source, decoder, &subtask.path),
+    ) {
+        (Ok(Some(old)), Ok(new)) => old != new.text,
+        _ => true,
+    }
+}
+
+/// Files added, removed or modified between two scans
+#[pyclass]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangeSet {
+    #[pyo3(get)]
+    pub added: Vec<String>,
+    #[pyo3(get)]
+    pub removed: Vec<String>,
+    #[pyo3(get)]
+    pub modified: Vec<String>,
+}
+
+impl ChangeSet {
+    /// Compare two path lists only; nothing counts as modified
+    pub fn between_paths(old: &[String], new: &[String]) -> Self {
+        let old_set: HashSet<&String> = old.iter().collect();
+        let new_set: HashSet<&String> = new.iter().collect();
+        ChangeSet {
+            added: new
+                .iter()
+                .filter(|p| !old_set.contains(p))
+                .cloned()
+                .collect(),
+            removed: old
+                .iter()
+                .filter(|p| !new_set.contains(p))
+                .cloned()
+                .collect(),
+            modified: Vec::new(),
+        }
+    }
+
+    /// Compare the fingerprints recorded at the last load with a fresh scan.
+    /// `old_paths` gives the order of removed paths, `new` the order of the rest.
+    pub fn detect(
+        old_paths: &[String],
+        old: &HashMap<String, Option<FileFingerprint>>,
+        new: &[(String, Option<FileFingerprint>)],
+    ) -> Self {
+        let mut changes = ChangeSet::between_paths(
+            old_paths,
+            &new.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(),
+        );
+        changes.modified = new
+            .iter()
+            .filter(|(path, fingerprint)| match (old.get(path), fingerprint) {
+                (Some(Some(before)), Some(after)) => before != after,
+                (Some(_), _) => true,
+                (None, _) => false, // added
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+#[pymethods]
+impl ChangeSet {
+    fn __bool__(&self) -> bool {
+        !self.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ChangeSet(added={:?}, removed={:?}, modified={:?})",
+            self.added, self.removed, self.modified
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn fingerprint(len: u64, secs: u64) -> Option<FileFingerprint> {
+        Some(FileFingerprint::new(
+            len,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            None,
+        ))
+    }
+
+    fn paths(items: &[&str]) -> Vec<String> {
+        items.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_between_paths() {
+        let changes = ChangeSet::between_paths(&paths(&["a", "b"]), &paths(&["b", "c"]));
+        assert_eq!(changes.added, ["c"]);
+        assert_eq!(changes.removed, ["a"]);
+        assert!(changes.modified.is_empty());
+    }
+
+    #[test]
+    fn test_detect() {
+        let old: HashMap<String, Option<FileFingerprint>> = [
+            ("same".to_string(), fingerprint(1, 10)),
+            ("resized".to_string(), fingerprint(1, 10)),
+            ("touched".to_string(), fingerprint(1, 10)),
+            ("unreadable".to_string(), None),
+            ("gone".to_string(), fingerprint(1, 10)),
+        ]
+        .into_iter()
+        .collect();
+        let old_paths = paths(&["gone", "resized", "same", "touched", "unreadable"]);
+        let new = vec![
+            ("new".to_string(), fingerprint(1, 10)),
+            ("resized".to_string(), fingerprint(2, 10)),
+            ("same".to_string(), fingerprint(1, 10)),
+            ("touched".to_string(), fingerprint(1, 20)),
+            ("unreadable".to_string(), fingerprint(1, 10)),
+        ];
+
+        let changes = ChangeSet::detect(&old_paths, &old, &new);
+        assert_eq!(changes.added, ["new"]);
+        assert_eq!(changes.removed, ["gone"]);
+        assert_eq!(changes.modified, ["resized", "touched", "unreadable"]);
+        assert!(!changes.is_empty());
+        assert!(ChangeSet::default().is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use anyhow::{bail, Result};
+use encoding_rs::{Encoding, UTF_8};
+use std::io;
+
+use crate::enums::ErrorKind;
+use crate::errors::SubtaskError;
+
+/// How far into a file NUL bytes are looked for, as git does
+pub const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Turns task file bytes into text.
+///
+/// A byte order mark (UTF-8, UTF-16LE or UTF-16BE) decides the encoding and is
+/// stripped. Without one the content must be valid UTF-8, or else valid in one of
+/// the fallback encodings, tried in order. Single-byte encodings such as
+/// `windows-1252` accept any input, so they belong at the end of the list.
+/// Labels follow the WHATWG Encoding Standard, where `latin-1` means `windows-1252`.
+///
+/// Files over `max_file_size` and files that look binary are refused with
+/// `ErrorKind::TooLarge` / `ErrorKind::Binary` instead of being decoded.
+#[derive(Clone, Debug)]
+pub struct TextDecoder {
+    fallback_encodings: Vec<&'static Encoding>,
+    normalize_newlines: bool,
+    max_file_size: Option<u64>,
+    detect_binary: bool,
+}
+
+impl Default for TextDecoder {
+    /// UTF-8 only, no size limit, binary files refused
+    fn default() -> Self {
+        TextDecoder {
+            fallback_encodings: Vec::new(),
+            normalize_newlines: false,
+            max_file_size: None,
+            detect_binary: true,
+        }
+    }
+}
+
+/// Text of a task file and the encoding it was read with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedText {
+    pub text: String,
+    /// Lowercase WHATWG name, e.g. `utf-8` or `windows-1251`
+    pub encoding: String,
+}
+
+impl TextDecoder {
+    pub fn new(
+        fallback_encodings: &[String],
+        normalize_newlines: bool,
+        max_file_size: Option<u64>,
+        detect_binary: bool,
+    ) -> Result<Self> {
+        let mut encodings = Vec::with_capacity(fallback_encodings.len());
+        for label in fallback_encodings {
+            match encoding_for_label(label) {
+                Some(encoding) => encodings.push(encoding),
+                None => bail!("Unknown encoding '{label}'"),
+            }
+        }
+        Ok(TextDecoder {
+            fallback_encodings: encodings,
+            normalize_newlines,
+            max_file_size,
+            detect_binary,
+        })
+    }
+
+    /// Canonical names of the fallback encodings, in the order they are tried
+    pub fn fallback_encodings(&self) -> Vec<String> {
+        self.fallback_encodings
+            .iter()
+            .map(|e| e.name().to_lowercase())
+            .collect()
+    }
+
+    pub fn normalize_newlines(&self) -> bool {
+        self.normalize_newlines
+    }
+
+    pub fn max_file_size(&self) -> Option<u64> {
+        self.max_file_size
+    }
+
+    pub fn detect_binary(&self) -> bool {
+        self.detect_binary
+    }
+
+    /// Refuse the file at `path` if `size` exceeds `max_file_size`; called with the
+    /// scanned size so oversized files are never read
+    pub fn check_size(&self, path: &str, size: u64) -> Result<()> {
+        match self.max_file_size {
+            Some(max) if size > max => Err(SubtaskError::new(
+                path,
+                ErrorKind::TooLarge,
+                format!("file is {size} bytes, larger than max_file_size ({max} bytes)"),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse the file at `path` if `detect_binary` is on and `data`, its content or
+    /// at least the first `BINARY_SNIFF_LEN` bytes of it, looks binary
+    pub fn check_binary(&self, path: &str, data: &[u8]) -> Result<()> {
+        if self.detect_binary && looks_binary(data) {
+            return Err(SubtaskError::new(
+                path,
+                ErrorKind::Binary,
+                "file looks binary (contains NUL bytes)",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Decode the content of the file at `path` after the size and binary checks
+    pub fn decode_file(&self, path: &str, data: &[u8]) -> Result<DecodedText> {
+        self.check_size(path, data.len() as u64)?;
+        self.check_binary(path, data)?;
+        self.decode(data)
+    }
+
+    /// Decode `data`; content valid in none of the candidate encodings is reported as
+    /// `InvalidData`, which surfaces as `ErrorKind.Encoding`
+    pub fn decode(&self, data: &[u8]) -> Result<DecodedText> {
+        let (encoding, body) = match Encoding::for_bom(data) {
+            Some((encoding, bom_len)) => (encoding, &data[bom_len..]),
+            None => (UTF_8, data),
+        };
+        let candidates = match encoding == UTF_8 && body.len() == data.len() {
+            true => std::iter::once(UTF_8)
+                .chain(self.fallback_encodings.iter().copied())
+                .collect(),
+            false => vec![encoding],
+        };
+        for candidate in candidates {
+            let Some(text) = candidate.decode_without_bom_handling_and_without_replacement(body)
+            else {
+                continue;
+            };
+            let text = match self.normalize_newlines {
+                true => normalize_newlines(&text),
+                false => text.into_owned(),
+            };
+            return Ok(DecodedText {
+                text,
+                encoding: candidate.name().to_lowercase(),
+            });
+        }
+        let mut tried = vec![encoding.name().to_lowercase()];
+        if body.len() == data.len() {
+            tried.extend(self.fallback_encodings());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("content is not valid {}", tried.join(" or ")),
+        )
+        .into())
+    }
+}
+
+/// Whether `data` has NUL bytes near the start. UTF-16 text, recognised by its BOM,
+/// is full of them and does not count.
+fn looks_binary(data: &[u8]) -> bool {
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        if encoding != UTF_8 {
+            return false;
+        }
+    }
+    data[..data.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Look up a WHATWG label, also accepting Python-style spellings such as
+/// `latin-1` or `windows_1251`
+fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    let label = label.trim();
+    [
+        label.to_string(),
+        label.replace('_', "-"),
+        label.replace(['-', '_'], ""),
+    ]
+    .iter()
+    .find_map(|candidate| Encoding::for_label(candidate.as_bytes()))
+}
+
+/// Convert `\r\n` and lone `\r` line endings to `\n`
+fn normalize_newlines(text: &str) -> String {
+    if !text.contains('\r') {
+        return text.to_string();
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoder(fallbacks: &[&str], normalize: bool) -> TextDecoder {
+        let labels: Vec<String> = fallbacks.iter().map(|l| l.to_string()).collect();
+        TextDecoder::new(&labels, normalize, None, false).unwrap()
+    }
+
+    #[test]
+    fn test_decode_utf8_and_bom() {
+        let plain = decoder(&[], false);
+        let decoded = plain.decode("SELECT 'é';".as_bytes()).unwrap();
+        assert_eq!(decoded.text, "SELECT 'é';");
+        assert_eq!(decoded.encoding, "utf-8");
+
+        let decoded = plain.decode(b"\xef\xbb\xbfSELECT 1;").unwrap();
+        assert_eq!(decoded.text, "SELECT 1;");
+
+        let decoded = plain.decode(b"\xff\xfeS\x00;\x00").unwrap();
+        assert_eq!(decoded.text, "S;");
+        assert_eq!(decoded.encoding, "utf-16le");
+    }
+
+    #[test]
+    fn test_decode_fallbacks() {
+        let cp1251 = b"SELECT '\xcf\xf0\xe8\xe2\xe5\xf2';";
+        let err = decoder(&[], false).decode(cp1251).unwrap_err();
+        assert!(err.downcast_ref::<io::Error>().unwrap().kind() == io::ErrorKind::InvalidData);
+
+        let decoded = decoder(&["cp1251", "latin-1"], false)
+            .decode(cp1251)
+            .unwrap();
+        assert_eq!(decoded.text, "SELECT 'Привет';");
+        assert_eq!(decoded.encoding, "windows-1251");
+
+        // UTF-8 content is never decoded with a fallback
+        let decoded = decoder(&["latin-1"], false).decode("é".as_bytes()).unwrap();
+        assert_eq!(decoded.text, "é");
+        assert_eq!(
+            decoder(&["windows_1251"], false).fallback_encodings(),
+            ["windows-1251"]
+        );
+        assert!(TextDecoder::new(&["klingon".to_string()], false, None, false).is_err());
+    }
+
+    #[test]
+    fn test_normalize_newlines() {
+        let decoded = decoder(&[], true).decode(b"a\r\nb\rc\n").unwrap();
+        assert_eq!(decoded.text, "a\nb\nc\n");
+        let decoded = decoder(&[], false).decode(b"a\r\nb").unwrap();
+        assert_eq!(decoded.text, "a\r\nb");
+    }
+
+    #[test]
+    fn test_decode_file_limits() {
+        let limited = TextDecoder::new(&[], false, Some(4), true).unwrap();
+        let kind = |err: anyhow::Error| err.downcast::<SubtaskError>().unwrap().kind;
+
+        assert_eq!(limited.decode_file("a.sql", b"abcd").unwrap().text, "abcd");
+        assert_eq!(
+            kind(limited.decode_file("a.sql", b"abcde").unwrap_err()),
+            ErrorKind::TooLarge
+        );
+        assert!(limited.check_size("a.sql", 5).is_err());
+        assert_eq!(
+            kind(limited.decode_file("a.sql", b"a\0b").unwrap_err()),
+            ErrorKind::Binary
+        );
+        // UTF-16 text is not binary
+        let unlimited = TextDecoder::new(&[], false, None, true).unwrap();
+        assert_eq!(
+            unlimited
+                .decode_file("a.sql", b"\xff\xfeS\x00")
+                .unwrap()
+                .text,
+            "S"
+        );
+    }
+}
@@ -233,6 +233,12 @@ impl EtlStage {
         Self::etl_stage_data()[self].id
     }
 
+    /// Position in the pipeline shared with custom stages: the id times 10, leaving
+    /// room in between (a custom stage with ordinal 25 runs after transform, before load)
+    pub fn ordinal(&self) -> i64 {
+        i64::from(self.id()) * 10
+    }
+
     pub fn name(&self) -> &'static str {
         Self::etl_stage_data()[self].name
     }
@@ -490,3 +496,392 @@ impl TaskType {
         &Self::task_type_data()[self].extensions
     }
 }
+
+/* ============================================================================================
+ *  SortOrder
+ * ============================================================================================ */
+
+#[derive(Debug, Clone)]
+struct SortOrderData {
+    id: u8,
+    name: &'static str,
+    aliases: Vec<&'static str>,
+}
+
+#[pyclass(eq, eq_int)]
+#[derive(Debug, PartialEq, Clone, Hash, Eq, Copy, Default, EnumIter, Serialize, Deserialize)]
+pub enum SortOrder {
+    /// By path relative to the base directory
+    #[default]
+    Path,
+    /// By stage id, then entity, then numeric file name prefix
+    Stage,
+}
+
+impl SortOrder {
+    fn sort_order_data() -> &'static HashMap<SortOrder, SortOrderData> {
+        static DATA: OnceLock<HashMap<SortOrder, SortOrderData>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            HashMap::from([
+                (
+                    SortOrder::Path,
+                    SortOrderData {
+                        id: 0,
+                        name: "path",
+                        aliases: vec!["path", "relative_path"],
+                    },
+                ),
+                (
+                    SortOrder::Stage,
+                    SortOrderData {
+                        id: 1,
+                        name: "stage",
+                        aliases: vec!["stage", "etl_stage"],
+                    },
+                ),
+            ])
+        })
+    }
+
+    pub fn from_alias(alias: &str) -> Result<SortOrder, String> {
+        let alias_lower = alias.to_lowercase();
+        for (order, data) in Self::sort_order_data().iter() {
+            if data.name == alias_lower || data.aliases.iter().any(|&a| a == alias_lower) {
+                return Ok(*order);
+            }
+        }
+        Err(format!("Unknown sort order alias: {}", alias))
+    }
+
+    pub fn id(&self) -> u8 {
+        Self::sort_order_data()[self].id
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::sort_order_data()[self].name
+    }
+
+    pub fn aliases(&self) -> &Vec<&'static str> {
+        &Self::sort_order_data()[self].aliases
+    }
+}
+
+/* ============================================================================================
+ *  ErrorPolicy
+ * ============================================================================================ */
+
+#[derive(Debug, Clone)]
+struct ErrorPolicyData {
+    id: u8,
+    name: &'static str,
+    aliases: Vec<&'static str>,
+}
+
+#[pyclass(eq, eq_int)]
+#[derive(Debug, PartialEq, Clone, Hash, Eq, Copy, Default, EnumIter, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// Fail on the first file that cannot be classified or loaded
+    #[default]
+    Raise,
+    /// Leave failing files out silently
+    Skip,
+    /// Leave failing files out and record them in `SubtaskManager.errors`
+    Collect,
+}
+
+impl ErrorPolicy {
+    fn error_policy_data() -> &'static HashMap<ErrorPolicy, ErrorPolicyData> {
+        static DATA: OnceLock<HashMap<ErrorPolicy, ErrorPolicyData>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            HashMap::from([
+                (
+                    ErrorPolicy::Raise,
+                    ErrorPolicyData {
+                        id: 0,
+                        name: "raise",
+                        aliases: vec!["raise", "fail", "strict"],
+                    },
+                ),
+                (
+                    ErrorPolicy::Skip,
+                    ErrorPolicyData {
+                        id: 1,
+                        name: "skip",
+                        aliases: vec!["skip", "ignore"],
+                    },
+                ),
+                (
+                    ErrorPolicy::Collect,
+                    ErrorPolicyData {
+                        id: 2,
+                        name: "collect",
+                        aliases: vec!["collect", "report"],
+                    },
+                ),
+            ])
+        })
+    }
+
+    pub fn from_alias(alias: &str) -> Result<ErrorPolicy, String> {
+        let alias_lower = alias.to_lowercase();
+        for (policy, data) in Self::error_policy_data().iter() {
+            if data.name == alias_lower || data.aliases.iter().any(|&a| a == alias_lower) {
+                return Ok(*policy);
+            }
+        }
+        Err(format!("Unknown error policy alias: {}", alias))
+    }
+
+    pub fn id(&self) -> u8 {
+        Self::error_policy_data()[self].id
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::error_policy_data()[self].name
+    }
+
+    pub fn aliases(&self) -> &Vec<&'static str> {
+        &Self::error_policy_data()[self].aliases
+    }
+}
+
+/* ============================================================================================
+ *  ErrorKind
+ * ============================================================================================ */
+
+#[derive(Debug, Clone)]
+struct ErrorKindData {
+    id: u8,
+    name: &'static str,
+}
+
+#[pyclass(eq, eq_int)]
+#[derive(Debug, PartialEq, Clone, Hash, Eq, Copy, EnumIter, Serialize, Deserialize)]
+pub enum ErrorKind {
+    /// Path does not fit the expected folder layout
+    FolderStructure,
+    /// Extension does not map to a TaskType
+    UnknownTaskType,
+    /// File could not be read
+    Io,
+    /// File content could not be decoded
+    Encoding,
+    /// Any other failure, or a changed file skipped by the error policy
+    Other,
+    /// File is larger than the configured maximum size
+    TooLarge,
+    /// File content looks binary (contains NUL bytes)
+    Binary,
+    /// Folders can be read in more than one way (strict classification)
+    Ambiguous,
+    /// File header names an unknown stage or system, or contradicts the folders
+    Header,
+}
+
+impl ErrorKind {
+    fn error_kind_data() -> &'static HashMap<ErrorKind, ErrorKindData> {
+        static DATA: OnceLock<HashMap<ErrorKind, ErrorKindData>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            HashMap::from([
+                (
+                    ErrorKind::FolderStructure,
+                    ErrorKindData {
+                        id: 0,
+                        name: "folder_structure",
+                    },
+                ),
+                (
+                    ErrorKind::UnknownTaskType,
+                    ErrorKindData {
+                        id: 1,
+                        name: "unknown_task_type",
+                    },
+                ),
+                (ErrorKind::Io, ErrorKindData { id: 2, name: "io" }),
+                (
+                    ErrorKind::Encoding,
+                    ErrorKindData {
+                        id: 3,
+                        name: "encoding",
+                    },
+                ),
+                (
+                    ErrorKind::Other,
+                    ErrorKindData {
+                        id: 4,
+                        name: "other",
+                    },
+                ),
+                (
+                    ErrorKind::TooLarge,
+                    ErrorKindData {
+                        id: 5,
+                        name: "too_large",
+                    },
+                ),
+                (
+                    ErrorKind::Binary,
+                    ErrorKindData {
+                        id: 6,
+                        name: "binary",
+                    },
+                ),
+                (
+                    ErrorKind::Ambiguous,
+                    ErrorKindData {
+                        id: 7,
+                        name: "ambiguous",
+                    },
+                ),
+                (
+                    ErrorKind::Header,
+                    ErrorKindData {
+                        id: 8,
+                        name: "header",
+                    },
+                ),
+            ])
+        })
+    }
+
+    pub fn id(&self) -> u8 {
+        Self::error_kind_data()[self].id
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::error_kind_data()[self].name
+    }
+}
+
+/* ============================================================================================
+ *  ChangeKind
+ * ============================================================================================ */
+
+#[derive(Debug, Clone)]
+struct ChangeKindData {
+    id: u8,
+    name: &'static str,
+}
+
+#[pyclass(eq, eq_int)]
+#[derive(Debug, PartialEq, Clone, Hash, Eq, Copy, EnumIter, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    fn change_kind_data() -> &'static HashMap<ChangeKind, ChangeKindData> {
+        static DATA: OnceLock<HashMap<ChangeKind, ChangeKindData>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            HashMap::from([
+                (
+                    ChangeKind::Added,
+                    ChangeKindData {
+                        id: 0,
+                        name: "added",
+                    },
+                ),
+                (
+                    ChangeKind::Modified,
+                    ChangeKindData {
+                        id: 1,
+                        name: "modified",
+                    },
+                ),
+                (
+                    ChangeKind::Removed,
+                    ChangeKindData {
+                        id: 2,
+                        name: "removed",
+                    },
+                ),
+            ])
+        })
+    }
+
+    pub fn id(&self) -> u8 {
+        Self::change_kind_data()[self].id
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::change_kind_data()[self].name
+    }
+}
+
+/* ============================================================================================
+ *  SegmentRole
+ * ============================================================================================ */
+
+#[derive(Debug, Clone)]
+struct SegmentRoleData {
+    id: u8,
+    name: &'static str,
+}
+
+/// What the classifier used a folder of a task path for
+#[pyclass(eq, eq_int)]
+#[derive(Debug, PartialEq, Clone, Hash, Eq, Copy, EnumIter, Serialize, Deserialize)]
+pub enum SegmentRole {
+    Stage,
+    System,
+    Entity,
+    /// Matched a layout placeholder or literal, or a system folder that a later one replaced
+    Ignored,
+    /// Not assigned because classification failed first
+    Unmatched,
+}
+
+impl SegmentRole {
+    fn segment_role_data() -> &'static HashMap<SegmentRole, SegmentRoleData> {
+        static DATA: OnceLock<HashMap<SegmentRole, SegmentRoleData>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            HashMap::from([
+                (
+                    SegmentRole::Stage,
+                    SegmentRoleData {
+                        id: 0,
+                        name: "stage",
+                    },
+                ),
+                (
+                    SegmentRole::System,
+                    SegmentRoleData {
+                        id: 1,
+                        name: "system",
+                    },
+                ),
+                (
+                    SegmentRole::Entity,
+                    SegmentRoleData {
+                        id: 2,
+                        name: "entity",
+                    },
+                ),
+                (
+                    SegmentRole::Ignored,
+                    SegmentRoleData {
+                        id: 3,
+                        name: "ignored",
+                    },
+                ),
+                (
+                    SegmentRole::Unmatched,
+                    SegmentRoleData {
+                        id: 4,
+                        name: "unmatched",
+                    },
+                ),
+            ])
+        })
+    }
+
+    pub fn id(&self) -> u8 {
+        Self::segment_role_data()[self].id
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::segment_role_data()[self].name
+    }
+}
@@ -0,0 +1,68 @@
+use crate::enums::ErrorKind;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+/// A file that could not be turned into a Subtask, with a structured reason.
+///
+/// Also used as the error value inside `anyhow::Error` by the classifier, so callers
+/// can recover the kind with `SubtaskError::from_anyhow`.
+#[pyclass]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubtaskError {
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub kind: ErrorKind,
+    #[pyo3(get)]
+    pub message: String,
+}
+
+impl SubtaskError {
+    pub fn new(path: &str, kind: ErrorKind, message: impl Into<String>) -> Self {
+        SubtaskError {
+            path: path.to_string(),
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Classify an error returned by the classifier or the loader for `path`
+    pub fn from_anyhow(path: &str, err: &anyhow::Error) -> Self {
+        if let Some(e) = err.downcast_ref::<SubtaskError>() {
+            return e.clone();
+        }
+        let kind = match err.downcast_ref::<io::Error>() {
+            // read_to_string reports undecodable content as InvalidData
+            Some(io_err) if io_err.kind() == io::ErrorKind::InvalidData => ErrorKind::Encoding,
+            Some(_) => ErrorKind::Io,
+            None => ErrorKind::Other,
+        };
+        SubtaskError::new(path, kind, err.to_string())
+    }
+}
+
+impl fmt::Display for SubtaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SubtaskError {}
+
+#[pymethods]
+impl SubtaskError {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "SubtaskError(path='{}', kind={}, message='{}')",
+            self.path,
+            self.kind.name(),
+            self.message
+        )
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("{}: {}", self.path, self.message)
+    }
+}
@@ -0,0 +1,139 @@
+use pyo3::prelude::*;
+
+use crate::enums::SegmentRole;
+use crate::errors::SubtaskError;
+use crate::models::Subtask;
+use crate::stages::Stage;
+use crate::systems::System;
+
+/// How the classifier read one folder of a task path
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct SegmentMatch {
+    #[pyo3(get)]
+    pub folder: String,
+    /// What the folder was used for
+    #[pyo3(get)]
+    pub role: SegmentRole,
+    /// Stage the folder is an alias of, whatever it was used for
+    pub stage: Option<Stage>,
+    /// System the folder is an alias of, whatever it was used for
+    pub system: Option<System>,
+    /// Why the folder got its role
+    #[pyo3(get)]
+    pub rule: String,
+}
+
+impl SegmentMatch {
+    /// A folder not looked at yet, with the aliases it matches
+    pub fn new(folder: &str, stage: Option<Stage>, system: Option<System>) -> Self {
+        SegmentMatch {
+            folder: folder.to_string(),
+            role: SegmentRole::Unmatched,
+            stage,
+            system,
+            rule: "not reached".to_string(),
+        }
+    }
+
+    pub fn assign(&mut self, role: SegmentRole, rule: impl Into<String>) {
+        self.role = role;
+        self.rule = rule.into();
+    }
+
+    /// Whether the folder could be read in more than one way: it is an alias of both a
+    /// stage and a system, or it was not used as what its alias stands for
+    pub fn is_ambiguous(&self) -> bool {
+        (self.stage.is_some() && self.system.is_some())
+            || (self.stage.is_some() && self.role != SegmentRole::Stage)
+            || (self.system.is_some() && self.role != SegmentRole::System)
+    }
+}
+
+#[pymethods]
+impl SegmentMatch {
+    #[getter(stage)]
+    fn stage_py(&self) -> Option<Stage> {
+        self.stage.clone()
+    }
+
+    #[getter(system)]
+    fn system_py(&self) -> Option<System> {
+        self.system.clone()
+    }
+
+    #[getter(ambiguous)]
+    fn ambiguous_py(&self) -> bool {
+        self.is_ambiguous()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SegmentMatch(folder='{}', role={}, rule='{}')",
+            self.folder,
+            self.role.name(),
+            self.rule
+        )
+    }
+}
+
+/// Result of `FileClassifier.explain`: the folders of a path, what each matched and
+/// the subtask or error classification ended with
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    #[pyo3(get)]
+    pub path: String,
+    /// Folders between the base path and the file, outermost first
+    #[pyo3(get)]
+    pub segments: Vec<SegmentMatch>,
+    #[pyo3(get)]
+    pub subtask: Option<Subtask>,
+    #[pyo3(get)]
+    pub error: Option<SubtaskError>,
+    /// Ambiguous folders, reported whether or not the classifier is strict
+    #[pyo3(get)]
+    pub warnings: Vec<SubtaskError>,
+}
+
+#[pymethods]
+impl Explanation {
+    fn __repr__(&self) -> String {
+        let outcome = match (&self.subtask, &self.error) {
+            (_, Some(error)) => format!("error='{}'", error.message),
+            _ => "ok".to_string(),
+        };
+        format!(
+            "Explanation(path='{}', segments={}, {outcome})",
+            self.path,
+            self.segments.len()
+        )
+    }
+
+    /// One line per folder and the outcome, for printing
+    fn __str__(&self) -> String {
+        let mut lines = vec![self.path.clone()];
+        for segment in &self.segments {
+            lines.push(format!(
+                "  {:<20} {:<9} {}{}",
+                segment.folder,
+                segment.role.name(),
+                segment.rule,
+                if segment.is_ambiguous() {
+                    " (ambiguous)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        for warning in &self.warnings {
+            lines.push(format!("  warning: {}", warning.message));
+        }
+        lines.push(match (&self.subtask, &self.error) {
+            (_, Some(error)) => format!("  -> error: {}", error.message),
+            (Some(subtask), None) => format!("  -> {}", subtask.__repr__()),
+            (None, None) => "  -> no result".to_string(),
+        });
+        lines.join("\n")
+    }
+}
@@ -1,21 +1,293 @@
-use crate::enums::{EtlStage, SystemType};
+use crate::aliases::AliasRegistry;
+use crate::enums::{ErrorKind, EtlStage, SegmentRole};
+use crate::errors::SubtaskError;
+use crate::explain::{Explanation, SegmentMatch};
+use crate::headers::parse_header;
+use crate::layout::{Layout, LayoutField};
 use crate::models::Subtask;
 use crate::py_utils::py_path_to_string;
+use crate::stages::Stage;
+use crate::systems::System;
 use anyhow::{bail, Result};
 use pyo3::prelude::*;
 use std::path::Path;
-use strum::IntoEnumIterator;
 
 /// FileClassifier classifies file paths into Subtask objects based on folder structure
 #[pyclass]
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct FileClassifier {
     pub(crate) base_path: String,
+    layout: Option<Layout>, // Positional folder layout; the alias heuristic when None
+    max_entity_depth: Option<usize>, // Folders an entity may span; unlimited when None
+    aliases: AliasRegistry, // Stage and system aliases on top of the built-in ones
+    strict: bool,           // Fail on ambiguous folders instead of picking a reading
+    read_headers: bool,     // Read metadata from the header at the top of each file
+}
+
+impl Default for FileClassifier {
+    /// Alias heuristic with single-folder entities, resolving paths as given
+    fn default() -> Self {
+        FileClassifier {
+            base_path: String::new(),
+            layout: None,
+            max_entity_depth: Some(1),
+            aliases: AliasRegistry::default(),
+            strict: false,
+            read_headers: false,
+        }
+    }
 }
 
 impl FileClassifier {
+    /// Same classifier settings, resolving paths against `base_path`
+    pub(crate) fn with_base_path(mut self, base_path: &str) -> Self {
+        self.base_path = base_path.to_string();
+        self
+    }
+
+    /// Classifier settings for the `layout` option
+    pub(crate) fn with_layout(mut self, layout: Option<&str>) -> PyResult<Self> {
+        self.layout = layout
+            .map(Layout::parse)
+            .transpose()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(self)
+    }
+
+    /// Classifier settings for the `max_entity_depth` option
+    pub(crate) fn with_max_entity_depth(
+        mut self,
+        max_entity_depth: Option<usize>,
+    ) -> PyResult<Self> {
+        if max_entity_depth == Some(0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "max_entity_depth must be at least 1",
+            ));
+        }
+        self.max_entity_depth = max_entity_depth;
+        Ok(self)
+    }
+
+    /// Classifier settings for the `aliases` option
+    pub(crate) fn with_aliases(mut self, aliases: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        if let Some(aliases) = aliases {
+            self.aliases = AliasRegistry::from_py(aliases)?;
+        }
+        Ok(self)
+    }
+
+    /// Classifier settings for the `strict` option
+    pub(crate) fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Classifier settings for the `read_headers` option
+    pub(crate) fn with_read_headers(mut self, read_headers: bool) -> Self {
+        self.read_headers = read_headers;
+        self
+    }
+
+    /// Stage and system aliases this classifier resolves folders with
+    pub(crate) fn alias_registry(&self) -> &AliasRegistry {
+        &self.aliases
+    }
+
+    /// Whether the `read_headers` option is on
+    pub(crate) fn reads_headers(&self) -> bool {
+        self.read_headers
+    }
+
+    /// Apply the header of the task file when `read_headers` is on; this reads lazily
+    /// loaded files
+    pub(crate) fn read_header(&self, sub: Subtask) -> Result<Subtask> {
+        if !self.read_headers {
+            return Ok(sub);
+        }
+        match sub.load_command()? {
+            Some(text) => self.apply_header(sub, &text),
+            None => Ok(sub),
+        }
+    }
+
+    /// Override stage, system and entity with the `stage`, `system` and `entity`
+    /// entries of the header in `text`; other entries become `metadata`. In strict
+    /// mode a header value that contradicts the folders fails with `ErrorKind::Header`.
+    pub(crate) fn apply_header(&self, mut sub: Subtask, text: &str) -> Result<Subtask> {
+        let mut fields = parse_header(text, sub.task_type);
+        let path = sub.path.clone();
+        let header_error = |message: String| SubtaskError::new(&path, ErrorKind::Header, message);
+        let contradicts =
+            |key: &str, from_header: &str, from_folders: Option<&str>| match from_folders {
+                Some(from_folders) if self.strict && from_header != from_folders => {
+                    Err(header_error(format!(
+                        "Header @{key}: {from_header} contradicts the folders ({from_folders})"
+                    )))
+                }
+                _ => Ok(()),
+            };
+        if let Some(value) = fields.remove("stage") {
+            let Some(stage) = self.aliases.stage(&value) else {
+                bail!(header_error(format!("Unknown stage '{value}' in header")));
+            };
+            let current = sub.pipeline_stage();
+            contradicts("stage", stage.name(), current.as_ref().map(|s| s.name()))?;
+            sub.set_stage(Some(stage));
+        }
+        if let Some(value) = fields.remove("system") {
+            let Some(system) = self.aliases.system(&value) else {
+                bail!(header_error(format!("Unknown system '{value}' in header")));
+            };
+            let current = sub.system();
+            contradicts("system", system.name(), current.as_ref().map(|s| s.name()))?;
+            sub.set_system(Some(system));
+        }
+        if let Some(value) = fields.remove("entity") {
+            let entity_path: Vec<String> = value
+                .split('/')
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .map(|p| p.to_string())
+                .collect();
+            contradicts("entity", &entity_path.join("/"), sub.entity.as_deref())?;
+            sub.set_entity_path(entity_path);
+        }
+        sub.metadata = fields;
+        Ok(sub)
+    }
+
+    /// Conflicts in how the folders of `file_path` were read: a folder that is both a
+    /// stage and a system alias, several folders naming a system, or an alias used as
+    /// an entity or left unused
+    pub(crate) fn conflicts(file_path: &str, trace: &[SegmentMatch]) -> Vec<SubtaskError> {
+        let system = trace.iter().find(|s| s.role == SegmentRole::System);
+        trace
+            .iter()
+            .filter(|segment| segment.role != SegmentRole::Unmatched && segment.is_ambiguous())
+            .map(|segment| {
+                let folder = &segment.folder;
+                let message = match (&segment.stage, &segment.system, segment.role) {
+                    (Some(stage), Some(system), _) => format!(
+                        "Folder '{folder}' is both a stage alias ({}) and a system alias ({})",
+                        stage.name(),
+                        system.name()
+                    ),
+                    (Some(alias), None, role) => format!(
+                        "Folder '{folder}' is a stage alias ({}) but is read as {}",
+                        alias.name(),
+                        role.name()
+                    ),
+                    (None, Some(alias), role) => match system {
+                        Some(used) if role == SegmentRole::Ignored && used.folder != *folder => {
+                            format!(
+                                "Folders '{folder}' and '{}' both name a system; '{}' is used",
+                                used.folder, used.folder
+                            )
+                        }
+                        _ => format!(
+                            "Folder '{folder}' is a system alias ({}) but is read as {}",
+                            alias.name(),
+                            role.name()
+                        ),
+                    },
+                    (None, None, _) => unreachable!("a folder without aliases is unambiguous"),
+                };
+                SubtaskError::new(file_path, ErrorKind::Ambiguous, message)
+            })
+            .collect()
+    }
+
+    /// Conflicts in how the folders of `file_path` are read, whether or not the
+    /// classifier is strict; empty when the path cannot be classified at all
+    pub(crate) fn warnings(&self, file_path: &str) -> Vec<SubtaskError> {
+        let mut trace = Vec::new();
+        match self.classify_segments(file_path, &mut trace) {
+            Ok(_) => Self::conflicts(file_path, &trace),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Fill stage, system and entity from the folders according to the layout
+    fn classify_by_layout(
+        &self,
+        layout: &Layout,
+        sub: &mut Subtask,
+        file_path: &str,
+        parts: &[String],
+        trace: &mut [SegmentMatch],
+    ) -> Result<()> {
+        let accepts = |field: &LayoutField, part: &str| match field {
+            LayoutField::Stage => self.aliases.stage(part).is_some(),
+            LayoutField::System => self.aliases.system(part).is_some(),
+            _ => true,
+        };
+        let Some(assigned) = layout.assign(parts, self.max_entity_depth, &accepts) else {
+            for segment in trace.iter_mut() {
+                segment.assign(SegmentRole::Unmatched, "no assignment fits the layout");
+            }
+            bail!(SubtaskError::new(
+                file_path,
+                ErrorKind::FolderStructure,
+                format!(
+                    "Folder structure does not match layout '{}'",
+                    layout.template()
+                )
+            ));
+        };
+        let mut segments = trace.iter_mut();
+        for (field, folders) in assigned {
+            let (role, rule) = match field {
+                LayoutField::Stage => {
+                    sub.set_stage(self.aliases.stage(&folders[0]));
+                    (SegmentRole::Stage, "layout segment {stage}".to_string())
+                }
+                LayoutField::System => {
+                    sub.set_system(self.aliases.system(&folders[0]));
+                    (SegmentRole::System, "layout segment {system}".to_string())
+                }
+                LayoutField::Entity => {
+                    sub.set_entity_path(folders.to_vec());
+                    (SegmentRole::Entity, "layout segment {entity}".to_string())
+                }
+                LayoutField::Named(name) => (
+                    SegmentRole::Ignored,
+                    format!("layout placeholder {{{name}}}"),
+                ),
+                LayoutField::Literal(name) => {
+                    (SegmentRole::Ignored, format!("layout literal '{name}'"))
+                }
+            };
+            for segment in segments.by_ref().take(folders.len()) {
+                segment.assign(role, rule.clone());
+            }
+        }
+        Ok(())
+    }
+
     /// Internal method for classification logic
     pub(crate) fn classify_internal(&self, file_path: &str) -> Result<Subtask> {
+        self.classify_traced(file_path, &mut Vec::new())
+    }
+
+    /// Classify `file_path`, recording in `trace` how each folder was read; in strict
+    /// mode conflicting readings fail with `ErrorKind::Ambiguous`
+    fn classify_traced(&self, file_path: &str, trace: &mut Vec<SegmentMatch>) -> Result<Subtask> {
+        let sub = self.classify_segments(file_path, trace)?;
+        if self.strict {
+            let conflicts = Self::conflicts(file_path, trace);
+            if !conflicts.is_empty() {
+                let messages: Vec<&str> = conflicts.iter().map(|c| c.message.as_str()).collect();
+                bail!(SubtaskError::new(
+                    file_path,
+                    ErrorKind::Ambiguous,
+                    messages.join("; ")
+                ));
+            }
+        }
+        Ok(sub)
+    }
+
+    fn classify_segments(&self, file_path: &str, trace: &mut Vec<SegmentMatch>) -> Result<Subtask> {
         let base = Path::new(&self.base_path);
         let file = Path::new(file_path);
 
@@ -29,89 +301,243 @@ impl FileClassifier {
             .skip(base_components)
             .map(|c| c.as_os_str().to_string_lossy().to_string())
             .collect();
+        // `other` and its aliases name no stage when found in a folder
+        trace.extend(parts.iter().map(|part| {
+            let stage = self
+                .aliases
+                .stage(part)
+                .filter(|stage| *stage != Stage::Builtin(EtlStage::Other));
+            SegmentMatch::new(part, stage, self.aliases.folder_system(part))
+        }));
 
         if parts.is_empty() {
             sub.is_common = true;
+        } else if let Some(layout) = &self.layout {
+            self.classify_by_layout(layout, &mut sub, file_path, &parts, trace)?;
+            return Self::with_task_type(sub, file_path);
         }
-        if parts.len() > 3 {
-            bail!("Incorrect folder structure");
+        // besides the entity folders, a path holds at most a stage and a system folder
+        if let Some(max) = self.max_entity_depth.filter(|max| parts.len() > max + 2) {
+            for segment in trace.iter_mut() {
+                segment.assign(
+                    SegmentRole::Unmatched,
+                    format!("more than {} folders", max + 2),
+                );
+            }
+            bail!(SubtaskError::new(
+                file_path,
+                ErrorKind::FolderStructure,
+                "Incorrect folder structure"
+            ));
         }
 
         let mut checked_parts: Vec<String> = Vec::new();
 
-        // simple stage detection: check if part matches known stage aliases
-        for part in &parts {
-            if sub.stage.is_none() {
-                let detected_stage = EtlStage::from_alias(part).unwrap_or(EtlStage::Other);
-                if detected_stage != EtlStage::Other {
-                    sub.stage = Some(detected_stage);
-                    checked_parts.push(part.clone());
-                    break;
-                }
-            } else {
-                break;
-            }
+        // simple stage detection: the first folder matching a stage alias
+        if let Some(segment) = trace.iter_mut().find(|s| s.stage.is_some()) {
+            sub.set_stage(segment.stage.clone());
+            checked_parts.push(segment.folder.clone());
+            let rule = format!(
+                "first stage alias ({})",
+                sub.pipeline_stage().unwrap().name()
+            );
+            segment.assign(SegmentRole::Stage, rule);
         }
 
-        // detect system type
-        for part in &parts {
-            if checked_parts.contains(part) {
+        // detect system type: the last folder matching a system alias wins
+        let mut system_segment: Option<usize> = None;
+        for (index, segment) in trace.iter_mut().enumerate() {
+            if checked_parts.contains(&segment.folder) {
                 continue;
             }
-            let l = part.to_lowercase();
-            for system_type in SystemType::iter() {
-                if system_type.aliases().contains(&l.as_str()) {
-                    checked_parts.push(part.clone());
-                    sub.system_type = Some(system_type);
-                }
+            if let Some(system) = segment.system.clone() {
+                checked_parts.push(segment.folder.clone());
+                segment.assign(
+                    SegmentRole::System,
+                    format!("last system alias ({})", system.name()),
+                );
+                sub.set_system(Some(system));
+                system_segment = Some(index);
+            }
+        }
+        for (index, segment) in trace.iter_mut().enumerate() {
+            if segment.role == SegmentRole::System && Some(index) != system_segment {
+                segment.assign(
+                    SegmentRole::Ignored,
+                    "system alias replaced by a later system folder",
+                );
             }
         }
 
-        // remaining candidate is entity
-        let candidates: Vec<&String> = parts
-            .iter()
-            .filter(|p| !checked_parts.contains(p))
-            .collect();
-        if candidates.len() > 1 {
-            bail!("Incorrect folder structure");
+        // remaining candidates form the entity path, outermost first
+        let mut candidates: Vec<String> = Vec::new();
+        for segment in trace.iter_mut() {
+            if segment.role != SegmentRole::Unmatched {
+                continue;
+            }
+            if checked_parts.contains(&segment.folder) {
+                segment.assign(SegmentRole::Ignored, "repeats a stage or system folder");
+            } else {
+                segment.assign(SegmentRole::Entity, "no stage or system alias");
+                candidates.push(segment.folder.clone());
+            }
         }
-        if let Some(ent) = candidates.first() {
-            sub.entity = Some((*ent).clone());
+        if self
+            .max_entity_depth
+            .is_some_and(|max| candidates.len() > max)
+        {
+            bail!(SubtaskError::new(
+                file_path,
+                ErrorKind::FolderStructure,
+                "Incorrect folder structure"
+            ));
         }
+        sub.set_entity_path(candidates);
+
+        Self::with_task_type(sub, file_path)
+    }
 
-        // set task type by extension
+    // set task type by extension
+    fn with_task_type(sub: Subtask, file_path: &str) -> Result<Subtask> {
         let sub = sub.set_task_type_from_ext();
         if sub.task_type.is_none() {
-            bail!("Unknown task type");
+            bail!(SubtaskError::new(
+                file_path,
+                ErrorKind::UnknownTaskType,
+                "Unknown task type"
+            ));
         }
 
         Ok(sub)
     }
 }
 
-#[pymethods]
-impl FileClassifier {
-    #[new]
-    fn new(base_path: &Bound<'_, PyAny>) -> PyResult<Self> {
-        let base_path_str = py_path_to_string("base_path", base_path)?;
-        Ok(FileClassifier {
-            base_path: base_path_str,
-        })
-    }
-
-    /// Classify a file path into a Subtask
-    pub fn classify(&self, file_path: &Bound<'_, PyAny>) -> PyResult<Subtask> {
-        let file_path_str = py_path_to_string("file_path", file_path)?;
-        self.classify_internal(&file_path_str)
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl FileClassifier {
+        /// `layout` is a folder template such as `"{system}/{entity}/{stage}"`; without one
+        /// stage and system folders are recognised by their aliases wherever they are.
+        /// `max_entity_depth` is how many folders an entity may span (None: any number).
+        /// `aliases` (an AliasRegistry, a dict or a JSON file path) adds stage and system aliases.
+        /// `strict` fails classification of ambiguous folders with `ErrorKind.Ambiguous`
+        /// and of headers contradicting the folders with `ErrorKind.Header`.
+        /// `read_headers` makes managers apply the header at the top of each task file.
+        #[new]
+        #[pyo3(signature = (base_path, layout=None, max_entity_depth=Some(1), aliases=None, strict=false, read_headers=false))]
+        fn new(
+            base_path: &Bound<'_, PyAny>,
+            layout: Option<&str>,
+            max_entity_depth: Option<usize>,
+            aliases: Option<&Bound<'_, PyAny>>,
+            strict: bool,
+            read_headers: bool,
+        ) -> PyResult<Self> {
+            let base_path_str = py_path_to_string("base_path", base_path)?;
+            FileClassifier::default()
+                .with_base_path(&base_path_str)
+                .with_layout(layout)?
+                .with_max_entity_depth(max_entity_depth)?
+                .with_aliases(aliases)
+                .map(|classifier| {
+                    classifier
+                        .with_strict(strict)
+                        .with_read_headers(read_headers)
+                })
+        }
+
+        /// Classify a file path and report how each folder was read, including the
+        /// error when classification fails
+        pub fn explain(&self, file_path: &Bound<'_, PyAny>) -> PyResult<Explanation> {
+            let path = py_path_to_string("file_path", file_path)?;
+            let mut segments = Vec::new();
+            let (subtask, error) = match self.classify_traced(&path, &mut segments) {
+                Ok(subtask) => (Some(subtask), None),
+                Err(e) => (None, Some(SubtaskError::from_anyhow(&path, &e))),
+            };
+            let warnings = Self::conflicts(&path, &segments);
+            Ok(Explanation {
+                path,
+                segments,
+                subtask,
+                error,
+                warnings,
+            })
+        }
+
+        /// Classify a file path into a Subtask; with `content`, the header at its top
+        /// overrides the folders and fills `metadata`
+        #[pyo3(signature = (file_path, content=None))]
+        pub fn classify(
+            &self,
+            file_path: &Bound<'_, PyAny>,
+            content: Option<&str>,
+        ) -> PyResult<Subtask> {
+            let file_path_str = py_path_to_string("file_path", file_path)?;
+            let classified = self.classify_internal(&file_path_str);
+            match content {
+                Some(text) => classified.and_then(|sub| self.apply_header(sub, text)),
+                None => classified,
+            }
             .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
-    }
+        }
 
-    #[getter]
-    fn base_path(&self) -> String {
-        self.base_path.clone()
-    }
+        #[getter]
+        fn base_path(&self) -> String {
+            self.base_path.clone()
+        }
+
+        /// Stage and system aliases added on top of the built-in ones
+        #[getter]
+        fn aliases(&self) -> AliasRegistry {
+            self.aliases.clone()
+        }
+
+        /// Stage for a folder name or alias: an `EtlStage` member or a registered
+        /// `CustomStage`
+        fn stage_for(&self, alias: &str) -> Option<Stage> {
+            self.aliases.stage(alias)
+        }
+
+        /// System for a folder name or alias: a `SystemType` member or a registered
+        /// `CustomSystem`
+        fn system_type_for(&self, alias: &str) -> Option<System> {
+            self.aliases.system(alias)
+        }
+
+        /// Whether ambiguous folders fail classification with `ErrorKind.Ambiguous`
+        #[getter]
+        fn strict(&self) -> bool {
+            self.strict
+        }
+
+        /// Whether managers apply the header at the top of each task file
+        #[getter]
+        fn read_headers(&self) -> bool {
+            self.read_headers
+        }
+
+        /// How many folders an entity may span; None when unlimited
+        #[getter]
+        fn max_entity_depth(&self) -> Option<usize> {
+            self.max_entity_depth
+        }
+
+        /// Folder layout template, if one was given
+        #[getter]
+        fn layout(&self) -> Option<String> {
+            self.layout.as_ref().map(|l| l.template().to_string())
+        }
 
-    fn __repr__(&self) -> String {
-        format!("FileClassifier(base_path='{}')", self.base_path)
+        fn __repr__(&self) -> String {
+            match &self.layout {
+                Some(layout) => format!(
+                    "FileClassifier(base_path='{}', layout='{}')",
+                    self.base_path,
+                    layout.template()
+                ),
+                None => format!("FileClassifier(base_path='{}')", self.base_path),
+            }
+        }
     }
-}
+};
@@ -1,9 +1,118 @@
+use crate::change_detection::FileFingerprint;
+use crate::decoding::{DecodedText, TextDecoder, BINARY_SNIFF_LEN};
 use crate::models::Subtask;
+use crate::task_source::{SharedSource, TaskSource};
 use anyhow::Result;
 use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
-pub fn load(mut subtask: Subtask) -> Result<Subtask> {
-    let content = fs::read_to_string(&subtask.path)?;
-    subtask.command = Some(content);
+/// Read the raw content of a task file on disk
+pub fn read_bytes(path: &str) -> Result<Vec<u8>> {
+    Ok(fs::read(path)?)
+}
+
+/// Read the file at `path` from `source` and decode it as text. With a size limit, a
+/// file whose size the source knows up front is refused before it is read.
+pub fn read_text(
+    source: &dyn TaskSource,
+    decoder: &TextDecoder,
+    path: &str,
+) -> Result<DecodedText> {
+    if decoder.max_file_size().is_some() {
+        if let Some(size) = source.size(path) {
+            decoder.check_size(path, size)?;
+        }
+    }
+    decoder.decode_file(path, &source.read(path)?)
+}
+
+/// Refuse the file at `path` when its first bytes look binary, so a lazily loaded file
+/// fails with the others instead of on first access. Sources that can only read whole
+/// files are left to the check made when the file is loaded.
+pub fn check_binary(source: &dyn TaskSource, decoder: &TextDecoder, path: &str) -> Result<()> {
+    if !decoder.detect_binary() {
+        return Ok(());
+    }
+    match source.head(path, BINARY_SNIFF_LEN)? {
+        Some(head) => decoder.check_binary(path, &head),
+        None => Ok(()),
+    }
+}
+
+pub fn load(mut subtask: Subtask, source: &SharedSource, decoder: &TextDecoder) -> Result<Subtask> {
+    let decoded = read_text(source.as_ref(), decoder, &subtask.path)?;
+    subtask.command = Some(decoded.text);
+    subtask.encoding = Some(decoded.encoding);
     Ok(subtask)
 }
+
+/// Record the size and modification time of the task file from its scan-time fingerprint
+pub fn with_file_info(mut subtask: Subtask, fingerprint: Option<&FileFingerprint>) -> Subtask {
+    if let Some(fingerprint) = fingerprint {
+        subtask.size_bytes = Some(fingerprint.len());
+        subtask.modified_at = fingerprint
+            .modified()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs_f64());
+    }
+    subtask
+}
+
+/// Attach the task file to `subtask` without reading it; the content is read on first use
+pub fn load_lazy(
+    mut subtask: Subtask,
+    source: &SharedSource,
+    decoder: &Arc<TextDecoder>,
+) -> Subtask {
+    subtask.lazy_command = Some(Arc::new(LazyCommand::new(
+        &subtask.path,
+        source.clone(),
+        decoder.clone(),
+    )));
+    subtask
+}
+
+/// Task file content read on first use.
+///
+/// Shared through an `Arc` by every clone of a Subtask, so the file is read at
+/// most once until `release` drops the cached text again.
+#[derive(Debug)]
+pub struct LazyCommand {
+    path: String,
+    source: SharedSource,
+    decoder: Arc<TextDecoder>,
+    content: Mutex<Option<DecodedText>>,
+}
+
+impl LazyCommand {
+    pub fn new(path: &str, source: SharedSource, decoder: Arc<TextDecoder>) -> Self {
+        LazyCommand {
+            path: path.to_string(),
+            source,
+            decoder,
+            content: Mutex::new(None),
+        }
+    }
+
+    /// Cached content, reading the file if it has not been read yet
+    pub fn get(&self) -> Result<DecodedText> {
+        if let Some(decoded) = self.content.lock().unwrap().as_ref() {
+            return Ok(decoded.clone());
+        }
+        // not read under the lock: a Python-backed source needs the GIL, which a
+        // thread waiting on this lock may be holding
+        let decoded = read_text(self.source.as_ref(), &self.decoder, &self.path)?;
+        *self.content.lock().unwrap() = Some(decoded.clone());
+        Ok(decoded)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.content.lock().unwrap().is_some()
+    }
+
+    /// Drop the cached content; the next `get` reads the file again
+    pub fn release(&self) {
+        *self.content.lock().unwrap() = None;
+    }
+}
@@ -1,53 +1,416 @@
+use ignore::gitignore::Gitignore;
+use ignore::{WalkBuilder, WalkState};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use std::collections::HashSet;
-use walkdir::WalkDir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+use crate::enums::SortOrder;
+use crate::file_classifier::FileClassifier;
+use crate::ordering::sort_paths;
+use crate::parallel::WorkerPool;
+use crate::path_filter::PathFilter;
 use crate::py_utils::py_path_to_string;
+use crate::task_source::{open_path, TaskSource};
+use crate::virtual_tree::is_ignored;
+
+/// Project-specific ignore file, read with the same rules as `.gitignore`.
+pub const SUBTASK_IGNORE_FILENAME: &str = ".subtaskignore";
+
+/// A path the scanner had to skip, with the reason it could not be read
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct ScanError {
+    /// Skipped path, or the base directory when the error is not tied to an entry
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub reason: String,
+}
+
+impl ScanError {
+    fn from_walk_error(err: &ignore::Error, base_dir: &str) -> Self {
+        let (path, reason) = Self::unwrap_walk_error(err);
+        ScanError {
+            path: path.map_or_else(|| base_dir.to_string(), |p| p.to_string_lossy().to_string()),
+            reason,
+        }
+    }
+
+    // Peel the path/depth/line wrappers off an `ignore::Error`
+    fn unwrap_walk_error(err: &ignore::Error) -> (Option<PathBuf>, String) {
+        match err {
+            ignore::Error::WithPath { path, err } => {
+                let (inner_path, reason) = Self::unwrap_walk_error(err);
+                (inner_path.or_else(|| Some(path.clone())), reason)
+            }
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+                Self::unwrap_walk_error(err)
+            }
+            ignore::Error::Loop { child, .. } => (Some(child.clone()), err.to_string()),
+            ignore::Error::Partial(errs) if errs.len() == 1 => Self::unwrap_walk_error(&errs[0]),
+            _ => (None, err.to_string()),
+        }
+    }
+}
+
+#[pymethods]
+impl ScanError {
+    fn __repr__(&self) -> String {
+        format!("ScanError(path='{}', reason='{}')", self.path, self.reason)
+    }
+
+    fn __str__(&self) -> String {
+        format!("{}: {}", self.path, self.reason)
+    }
+}
 
 // FileScanner struct
 #[pyclass]
+#[derive(Clone)]
 pub struct FileScanner {
     extensions: HashSet<String>,
+    respect_ignore_files: bool,
+    filter: PathFilter,
+    sort_by: SortOrder,
+    strict: bool,
+    follow_symlinks: bool,
+    include_hidden: bool,
+    max_depth: Option<usize>,
+    workers: WorkerPool,
+    scan_errors: Vec<ScanError>, // Report of the last scan
 }
 
-#[pymethods]
 impl FileScanner {
-    #[new]
-    pub fn new(extensions: Vec<String>) -> Self {
-        let mut exts = HashSet::new();
-        for e in extensions {
-            let normalized = e.trim_start_matches('.').to_lowercase();
-            exts.insert(normalized);
+    /// Build the directory walker for `base_dir` according to the scanner settings
+    fn walker(&self, base_dir: &str) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(base_dir);
+        builder
+            .hidden(!self.include_hidden)
+            // loops through followed links are reported by the walker as errors
+            .follow_links(self.follow_symlinks)
+            .max_depth(self.max_depth)
+            .ignore(self.respect_ignore_files)
+            .git_ignore(self.respect_ignore_files)
+            .git_exclude(self.respect_ignore_files)
+            .parents(self.respect_ignore_files)
+            // user-wide excludes would make results differ between machines
+            .git_global(false)
+            // task trees are not always git checkouts
+            .require_git(false);
+        if self.respect_ignore_files {
+            builder.add_custom_ignore_filename(SUBTASK_IGNORE_FILENAME);
         }
-        FileScanner { extensions: exts }
-    }
 
-    pub fn scan_files(&self, base_dir: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
-        // Convert base_dir to string, supporting both str and pathlib.Path
-        let base_dir_str = py_path_to_string("base_dir", base_dir)?;
+        // prune excluded directories during the walk instead of filtering afterwards
+        let filter = self.filter.clone();
+        let root = PathBuf::from(base_dir);
+        builder.filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            let rel = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            !is_dir || filter.allows_dir(rel)
+        });
+        builder
+    }
 
-        let mut found: Vec<String> = Vec::new();
+    /// Whether a walked file should be reported by the scan
+    fn accepts_file(&self, base_dir: &Path, path: &Path) -> bool {
+        let has_extension = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| self.extensions.contains(&ext.to_lowercase()));
+        has_extension
+            && self
+                .filter
+                .allows_file(path.strip_prefix(base_dir).unwrap_or(path))
+    }
 
-        for entry in WalkDir::new(&base_dir_str)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_file() {
-                if let Some(ext) = entry.path().extension().and_then(|s| s.to_str()) {
-                    if self.extensions.contains(&ext.to_lowercase()) {
-                        found.push(entry.path().to_string_lossy().to_string());
-                    }
+    /// Turn one walker result into a found file, a scan error, or nothing
+    fn visit(
+        &self,
+        base_dir: &str,
+        result: Result<ignore::DirEntry, ignore::Error>,
+    ) -> Option<Result<String, ScanError>> {
+        let base = Path::new(base_dir);
+        let entry = match result {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(ScanError::from_walk_error(&err, base_dir))),
+        };
+        if entry.path_is_symlink() && !self.follow_symlinks {
+            // a dangling link to a task file would otherwise vanish without a trace
+            if self.accepts_file(base, entry.path()) {
+                if let Err(err) = fs::metadata(entry.path()) {
+                    return Some(Err(ScanError {
+                        path: entry.path().to_string_lossy().to_string(),
+                        reason: format!("broken symbolic link: {err}"),
+                    }));
                 }
             }
+            return None;
+        }
+        if entry.file_type().is_some_and(|ft| ft.is_file()) && self.accepts_file(base, entry.path())
+        {
+            return Some(Ok(entry.path().to_string_lossy().to_string()));
         }
+        None
+    }
+
+    /// Walk `base_dir` in parallel and return the matching files together with the paths that had to be skipped.
+    /// Errors are sorted here and files by `scan_sorted`, so the thread count never changes the output.
+    pub(crate) fn scan(&self, base_dir: &str) -> (Vec<String>, Vec<ScanError>) {
+        let found: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let errors: Mutex<Vec<ScanError>> = Mutex::new(Vec::new());
+
+        self.walker(base_dir)
+            .threads(self.workers.threads().unwrap_or(0))
+            .build_parallel()
+            .run(|| {
+                Box::new(|result| {
+                    match self.visit(base_dir, result) {
+                        Some(Ok(path)) => found.lock().unwrap().push(path),
+                        Some(Err(err)) => errors.lock().unwrap().push(err),
+                        None => {}
+                    }
+                    WalkState::Continue
+                })
+            });
+
+        let found = found.into_inner().unwrap();
+        let mut errors = errors.into_inner().unwrap();
+        errors.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.reason.cmp(&b.reason)));
+
+        (found, errors)
+    }
+
+    /// Pick the entries below `prefix` from a file list that does not live on disk.
+    /// Paths are kept as given; depth, globs and hidden names are relative to `prefix`.
+    pub(crate) fn scan_entries<'a>(
+        &self,
+        names: impl Iterator<Item = &'a str>,
+        ignore_rules: &[Gitignore],
+        prefix: &str,
+    ) -> Vec<String> {
+        let root = Path::new(prefix);
+        names
+            .filter(|name| {
+                let Ok(rel) = Path::new(name).strip_prefix(root) else {
+                    return false;
+                };
+                let depth = rel.components().count();
+                let hidden = rel
+                    .components()
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+                let pruned = rel
+                    .ancestors()
+                    .skip(1)
+                    .any(|dir| !self.filter.allows_dir(dir));
+                self.max_depth.is_none_or(|max| depth <= max)
+                    && (self.include_hidden || !hidden)
+                    && !pruned
+                    && self.accepts_file(root, Path::new(name))
+                    && !is_ignored(ignore_rules, name)
+            })
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Scan `source` and put the files in `sort_by` order; `classifier` supplies the
+    /// stages and entities `SortOrder::Stage` sorts by
+    pub(crate) fn scan_sorted(
+        &self,
+        source: &dyn TaskSource,
+        classifier: &FileClassifier,
+    ) -> anyhow::Result<(Vec<String>, Vec<ScanError>)> {
+        let (mut found, errors) = source.scan(self)?;
+        sort_paths(&mut found, self.sort_by, classifier);
+        Ok((found, errors))
+    }
 
+    /// File extensions the scanner picks, lowercase and without the dot
+    pub(crate) fn extension_set(&self) -> &HashSet<String> {
+        &self.extensions
+    }
+
+    /// Worker threads for the stages that follow the scan, sized by `threads`
+    pub(crate) fn workers(&self) -> &WorkerPool {
+        &self.workers
+    }
+
+    /// Scan `source` with the scanner settings, keep the error report and fail on
+    /// scan errors in strict mode
+    pub(crate) fn scan_checked(
+        &mut self,
+        source: &dyn TaskSource,
+        classifier: &FileClassifier,
+    ) -> anyhow::Result<Vec<String>> {
+        let (found, errors) = self.scan_sorted(source, classifier)?;
+        self.record_errors(errors)?;
         Ok(found)
     }
 
-    #[getter]
-    fn extensions(&self) -> Vec<String> {
-        let mut extensions: Vec<String> = self.extensions.iter().cloned().collect();
-        extensions.sort();
-        extensions
+    /// `scan_checked` without holding the GIL
+    pub(crate) fn scan_source(
+        &mut self,
+        py: Python<'_>,
+        source: &dyn TaskSource,
+        classifier: &FileClassifier,
+    ) -> PyResult<Vec<String>> {
+        py.allow_threads(|| self.scan_checked(source, classifier))
+            .map_err(|e| PyRuntimeError::new_err(format!("{e:#}")))
+    }
+
+    /// Keep `errors` as the report of the last scan and fail on them in strict mode
+    fn record_errors(&mut self, errors: Vec<ScanError>) -> anyhow::Result<()> {
+        self.scan_errors = errors;
+        if self.strict && !self.scan_errors.is_empty() {
+            let details: Vec<String> = self.scan_errors.iter().map(|e| e.__str__()).collect();
+            anyhow::bail!(
+                "Scan failed with {} error(s): {}",
+                details.len(),
+                details.join("; ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Scanner for files with `extensions`, every other setting at its default
+    pub fn with_extensions(extensions: Vec<String>) -> Self {
+        FileScanner {
+            extensions: extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect(),
+            respect_ignore_files: true,
+            filter: PathFilter::default(),
+            sort_by: SortOrder::Path,
+            strict: false,
+            follow_symlinks: false,
+            include_hidden: true,
+            max_depth: None,
+            workers: WorkerPool::default(),
+            scan_errors: Vec::new(),
+        }
     }
 }
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl FileScanner {
+        #[new]
+        #[pyo3(signature = (extensions, respect_ignore_files=true, include=None, exclude=None, sort_by=SortOrder::Path, strict=false, follow_symlinks=false, include_hidden=true, max_depth=None, threads=None))]
+        #[allow(clippy::too_many_arguments)]
+        pub fn new(
+            extensions: Vec<String>,
+            respect_ignore_files: bool,
+            include: Option<Vec<String>>,
+            exclude: Option<Vec<String>>,
+            sort_by: SortOrder,
+            strict: bool,
+            follow_symlinks: bool,
+            include_hidden: bool,
+            max_depth: Option<usize>,
+            threads: Option<usize>,
+        ) -> PyResult<Self> {
+            let filter =
+                PathFilter::new(&include.unwrap_or_default(), &exclude.unwrap_or_default())
+                    .map_err(|e| PyValueError::new_err(format!("Invalid glob pattern: {e}")))?;
+            Ok(FileScanner {
+                respect_ignore_files,
+                filter,
+                sort_by,
+                strict,
+                follow_symlinks,
+                include_hidden,
+                max_depth,
+                workers: WorkerPool::new(threads),
+                ..FileScanner::with_extensions(extensions)
+            })
+        }
+
+        /// Scan a directory, or a `.zip` / `.tar` / `.tar.gz` archive whose entries are
+        /// returned as archive-relative paths, optionally only below `archive_prefix`
+        #[pyo3(signature = (base_dir, archive_prefix=None))]
+        pub fn scan_files(
+            &mut self,
+            base_dir: &Bound<'_, PyAny>,
+            archive_prefix: Option<String>,
+        ) -> PyResult<Vec<String>> {
+            // Convert base_dir to string, supporting both str and pathlib.Path
+            let base_dir_str = py_path_to_string("base_dir", base_dir)?;
+            let source = open_path(
+                base_dir.py(),
+                &base_dir_str,
+                archive_prefix.as_deref(),
+                self,
+                None,
+            )?;
+            let classifier = FileClassifier::default().with_base_path(&source.root());
+            self.scan_source(base_dir.py(), source.as_ref(), &classifier)
+        }
+
+        #[getter]
+        fn extensions(&self) -> Vec<String> {
+            let mut extensions: Vec<String> = self.extensions.iter().cloned().collect();
+            extensions.sort();
+            extensions
+        }
+
+        #[getter]
+        pub fn respect_ignore_files(&self) -> bool {
+            self.respect_ignore_files
+        }
+
+        #[getter]
+        pub fn sort_by(&self) -> SortOrder {
+            self.sort_by
+        }
+
+        #[getter]
+        pub fn strict(&self) -> bool {
+            self.strict
+        }
+
+        #[getter]
+        pub fn follow_symlinks(&self) -> bool {
+            self.follow_symlinks
+        }
+
+        #[getter]
+        pub fn include_hidden(&self) -> bool {
+            self.include_hidden
+        }
+
+        /// Maximum directory depth below the base; files directly in the base are at depth 1
+        #[getter]
+        pub fn max_depth(&self) -> Option<usize> {
+            self.max_depth
+        }
+
+        /// Worker threads used for scanning; `None` picks a default based on the CPU count
+        #[getter]
+        pub fn threads(&self) -> Option<usize> {
+            self.workers.threads()
+        }
+
+        /// Paths skipped during the last scan, with the reason
+        #[getter]
+        pub fn scan_errors(&self) -> Vec<ScanError> {
+            self.scan_errors.clone()
+        }
+
+        /// Include globs; when non-empty only matching files are reported
+        #[getter]
+        pub fn include(&self) -> Vec<String> {
+            self.filter.include_patterns().to_vec()
+        }
+
+        /// Exclude globs; matching directories are not descended into
+        #[getter]
+        pub fn exclude(&self) -> Vec<String> {
+            self.filter.exclude_patterns().to_vec()
+        }
+    }
+};
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use crate::enums::TaskType;
+
+/// Comment prefix header lines start with in files of `task_type`; None when the
+/// format has no comments
+fn comment_prefix(task_type: Option<TaskType>) -> Option<&'static str> {
+    match task_type? {
+        TaskType::Sql => Some("--"),
+        TaskType::Shell
+        | TaskType::Powershell
+        | TaskType::Python
+        | TaskType::Graphql
+        | TaskType::Yaml => Some("#"),
+        TaskType::Json | TaskType::Other => None,
+    }
+}
+
+/// Add `value` under `key`; a key given more than once collects its values
+/// comma-separated
+fn insert(fields: &mut BTreeMap<String, String>, key: &str, value: &str) {
+    let key = key.trim().to_lowercase();
+    let value = value.trim();
+    if key.is_empty() {
+        return;
+    }
+    fields
+        .entry(key)
+        .and_modify(|existing| {
+            existing.push_str(", ");
+            existing.push_str(value);
+        })
+        .or_insert_with(|| value.to_string());
+}
+
+/// Metadata from the header at the top of a task file.
+///
+/// The header is the run of blank and comment lines the file starts with (after a
+/// `#!` line); comment lines of the form `@key: value` (`-- @stage: transform` in SQL,
+/// `# @tags: daily` in Python, shell or YAML) become entries, other comments are
+/// skipped. YAML files may instead start with a `---` front matter block of
+/// `key: value` lines closed by `---` or `...`. Keys are lowercased.
+pub fn parse_header(text: &str, task_type: Option<TaskType>) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let mut lines = text.trim_start_matches('\u{feff}').lines().peekable();
+    if lines.peek().is_some_and(|line| line.starts_with("#!")) {
+        lines.next();
+    }
+
+    if task_type == Some(TaskType::Yaml) && lines.peek().map(|l| l.trim_end()) == Some("---") {
+        lines.next();
+        let front_matter: Vec<&str> = lines
+            .by_ref()
+            .take_while(|line| !matches!(line.trim_end(), "---" | "..."))
+            .collect();
+        for line in front_matter {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                insert(&mut fields, key, value);
+            }
+        }
+        return fields;
+    }
+
+    let Some(prefix) = comment_prefix(task_type) else {
+        return fields;
+    };
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix(prefix) else {
+            break;
+        };
+        let Some(entry) = comment.trim_start().strip_prefix('@') else {
+            continue;
+        };
+        if let Some((key, value)) = entry.split_once(':') {
+            insert(&mut fields, key, value);
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(text: &str, task_type: TaskType) -> Vec<(String, String)> {
+        parse_header(text, Some(task_type)).into_iter().collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_comment_header() {
+        let sql = "-- Load orders\n-- @Stage: transform\n\n--@depends_on: a.sql\n-- @depends_on: b.sql\nSELECT 1;\n-- @system: pg\n";
+        assert_eq!(
+            header(sql, TaskType::Sql),
+            pairs(&[("depends_on", "a.sql, b.sql"), ("stage", "transform")])
+        );
+        let py = "#!/usr/bin/env python\n# @tags: daily\nimport os\n";
+        assert_eq!(header(py, TaskType::Python), pairs(&[("tags", "daily")]));
+        // the prefix follows the file type
+        assert_eq!(header("# @stage: load\n", TaskType::Sql), pairs(&[]));
+        assert_eq!(header("{\"a\": 1}", TaskType::Json), pairs(&[]));
+    }
+
+    #[test]
+    fn test_yaml_front_matter() {
+        let yaml = "---\nstage: load\n# note\nowner: data-team\n---\njob: x\n";
+        assert_eq!(
+            header(yaml, TaskType::Yaml),
+            pairs(&[("owner", "data-team"), ("stage", "load")])
+        );
+        let comments = "# @system: duck\njob: x\n";
+        assert_eq!(
+            header(comments, TaskType::Yaml),
+            pairs(&[("system", "duck")])
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::change_detection::FileFingerprint;
+use crate::errors::SubtaskError;
+use crate::models::Subtask;
+
+/// Bumped whenever the file layout changes; older files are ignored
+const CACHE_FORMAT: u32 = 1;
+
+/// Classification of one file, valid while the file keeps its fingerprint
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CachedEntry {
+    fingerprint: FileFingerprint,
+    result: Result<Subtask, SubtaskError>,
+}
+
+/// Classified task index persisted between runs.
+///
+/// Entries hold subtasks without their content, keyed by path, and are reused only
+/// while the file's size, mtime (and hash, where the source has one) are unchanged.
+/// `key` describes the classifier settings; a cache written with other settings, by
+/// another version or in another format is discarded as a whole.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexCache {
+    format: u32,
+    key: String,
+    entries: HashMap<String, CachedEntry>,
+    #[serde(skip)]
+    path: PathBuf,
+    // entries differ from the file on disk
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl IndexCache {
+    /// Cache stored at `path` for `key`; empty when the file is missing, unreadable
+    /// or was written for other settings
+    pub fn load(path: &Path, key: &str) -> Self {
+        let empty = IndexCache {
+            format: CACHE_FORMAT,
+            key: format!("{}:{key}", env!("CARGO_PKG_VERSION")),
+            entries: HashMap::new(),
+            path: path.to_path_buf(),
+            dirty: false,
+        };
+        let Ok(data) = fs::read(path) else {
+            return empty;
+        };
+        match serde_json::from_slice::<IndexCache>(&data) {
+            Ok(cache) if cache.format == empty.format && cache.key == empty.key => IndexCache {
+                path: empty.path,
+                ..cache
+            },
+            _ => empty,
+        }
+    }
+
+    /// File the cache is stored in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Cached classification of `path`, if it was made for the same file state
+    pub fn lookup(
+        &self,
+        path: &str,
+        fingerprint: Option<&FileFingerprint>,
+    ) -> Option<Result<Subtask, SubtaskError>> {
+        let entry = self.entries.get(path)?;
+        (Some(&entry.fingerprint) == fingerprint).then(|| entry.result.clone())
+    }
+
+    /// Remember how `path` was classified; files that cannot be fingerprinted are not cached
+    pub fn insert(
+        &mut self,
+        path: &str,
+        fingerprint: Option<FileFingerprint>,
+        result: Result<Subtask, SubtaskError>,
+    ) {
+        let Some(fingerprint) = fingerprint else {
+            self.dirty |= self.entries.remove(path).is_some();
+            return;
+        };
+        // content belongs to the file, not to the index
+        let result = result.map(|mut subtask| {
+            subtask.command = None;
+            subtask.lazy_command = None;
+            subtask.encoding = None;
+            subtask
+        });
+        self.entries.insert(
+            path.to_string(),
+            CachedEntry {
+                fingerprint,
+                result,
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Drop entries of files that are no longer part of the scan
+    pub fn retain_paths(&mut self, paths: &[String]) {
+        let keep: HashSet<&str> = paths.iter().map(|p| p.as_str()).collect();
+        let before = self.entries.len();
+        self.entries.retain(|path, _| keep.contains(path.as_str()));
+        self.dirty |= self.entries.len() != before;
+    }
+
+    /// Write the cache back if it changed, replacing the file atomically so readers
+    /// never see half of it
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let path = self.path.as_path();
+        let data = serde_json::to_vec(self)?;
+        let tmp = temp_sibling(path);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let written = fs::write(&tmp, data)
+            .with_context(|| format!("Cannot write {}", tmp.display()))
+            .and_then(|_| {
+                fs::rename(&tmp, path).with_context(|| format!("Cannot write {}", path.display()))
+            });
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        written?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Temporary file next to `path` to write before renaming over it, named after the
+/// whole file name and unique per process and save so concurrent writers never share one
+fn temp_sibling(path: &Path) -> PathBuf {
+    static SAVES: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        SAVES.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::ErrorKind;
+
+    #[test]
+    fn test_roundtrip_and_validation() {
+        let dir = std::env::temp_dir().join(format!("subtask-cache-{}", std::process::id()));
+        let path = dir.join("index.json");
+        let fingerprint = FileFingerprint::new(9, None, Some(1));
+
+        let mut cache = IndexCache::load(&path, "base=etl");
+        assert!(cache.entries.is_empty());
+        let mut subtask = Subtask::new("etl/a.sql");
+        subtask.command = Some("SELECT 1;".to_string());
+        cache.insert("etl/a.sql", Some(fingerprint.clone()), Ok(subtask));
+        cache.insert(
+            "etl/b.txt",
+            Some(fingerprint.clone()),
+            Err(SubtaskError::new(
+                "etl/b.txt",
+                ErrorKind::UnknownTaskType,
+                "no",
+            )),
+        );
+        cache.insert("etl/c.sql", None, Ok(Subtask::new("etl/c.sql")));
+        cache.save().unwrap();
+        assert!(!cache.dirty);
+
+        let loaded = IndexCache::load(&path, "base=etl");
+        assert_eq!(loaded.entries.len(), 2);
+        let hit = loaded
+            .lookup("etl/a.sql", Some(&fingerprint))
+            .unwrap()
+            .unwrap();
+        assert_eq!(hit.path, "etl/a.sql");
+        assert_eq!(hit.command, None);
+        assert!(loaded
+            .lookup("etl/b.txt", Some(&fingerprint))
+            .unwrap()
+            .is_err());
+        let changed = FileFingerprint::new(10, None, Some(1));
+        assert!(loaded.lookup("etl/a.sql", Some(&changed)).is_none());
+        assert!(loaded.lookup("etl/a.sql", None).is_none());
+
+        assert!(IndexCache::load(&path, "base=other").entries.is_empty());
+        fs::write(&path, b"not json").unwrap();
+        assert!(IndexCache::load(&path, "base=etl").entries.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_next_to_tmp_named_cache() {
+        let dir = std::env::temp_dir().join(format!("subtask-cache-tmp-{}", std::process::id()));
+        let path = dir.join("index.tmp");
+        assert_ne!(temp_sibling(&path), path);
+        assert_ne!(temp_sibling(&path), temp_sibling(&path));
+
+        let mut cache = IndexCache::load(&path, "base=etl");
+        let fingerprint = FileFingerprint::new(9, None, Some(1));
+        cache.insert(
+            "etl/a.sql",
+            Some(fingerprint),
+            Ok(Subtask::new("etl/a.sql")),
+        );
+        cache.save().unwrap();
+        assert_eq!(IndexCache::load(&path, "base=etl").entries.len(), 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
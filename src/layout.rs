@@ -0,0 +1,247 @@
+use anyhow::{bail, Result};
+
+/// What a layout segment holds
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutField {
+    Stage,
+    System,
+    Entity,
+    /// Any other `{name}`: matches one folder whose name is not used
+    Named(String),
+    /// A folder name without braces, matched exactly
+    Literal(String),
+}
+
+/// One `/`-separated part of a layout template
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Segment {
+    field: LayoutField,
+    optional: bool,
+}
+
+/// Folder layout template such as `{system}/{entity}/{stage}`.
+///
+/// Each segment describes one folder between the base path and the file:
+/// `{stage}`, `{system}` and `{entity}` fill the subtask fields, other `{name}`
+/// placeholders match any folder, and plain names must appear as written.
+/// `{entity}` may span several folders, up to the classifier's entity depth.
+/// A trailing `?` inside the braces (`{system?}`) makes the segment optional.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl Layout {
+    pub fn parse(template: &str) -> Result<Self> {
+        let template = template.trim().trim_matches('/');
+        if template.is_empty() {
+            bail!("Invalid layout '{template}': it has no segments");
+        }
+        let mut segments: Vec<Segment> = Vec::new();
+        for part in template.split('/') {
+            let part = part.trim();
+            let segment = match part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                Some(inner) => {
+                    let (name, optional) = match inner.strip_suffix('?') {
+                        Some(name) => (name, true),
+                        None => (inner, false),
+                    };
+                    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                        bail!("Invalid layout '{template}': bad placeholder '{part}'");
+                    }
+                    let field = match name {
+                        "stage" => LayoutField::Stage,
+                        "system" => LayoutField::System,
+                        "entity" => LayoutField::Entity,
+                        other => LayoutField::Named(other.to_string()),
+                    };
+                    Segment { field, optional }
+                }
+                None if part.is_empty() || part.contains(['{', '}']) => {
+                    bail!("Invalid layout '{template}': bad segment '{part}'")
+                }
+                None => Segment {
+                    field: LayoutField::Literal(part.to_string()),
+                    optional: false,
+                },
+            };
+            let repeated = segments.iter().any(|s| {
+                s.field == segment.field
+                    && matches!(
+                        segment.field,
+                        LayoutField::Stage | LayoutField::System | LayoutField::Entity
+                    )
+            });
+            if repeated {
+                bail!("Invalid layout '{template}': '{part}' appears more than once");
+            }
+            segments.push(segment);
+        }
+        Ok(Layout {
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Assign the folders `parts` to the layout segments, left to right.
+    /// `accepts(field, part)` decides whether a stage or system segment can hold a
+    /// folder; `{entity}` takes as few folders as possible, at most `max_entity_depth`;
+    /// optional segments are filled when possible and skipped otherwise.
+    /// `None` when the folders do not fit the layout.
+    pub fn assign<'a>(
+        &self,
+        parts: &'a [String],
+        max_entity_depth: Option<usize>,
+        accepts: &dyn Fn(&LayoutField, &str) -> bool,
+    ) -> Option<Vec<(&LayoutField, &'a [String])>> {
+        let mut assigned = Vec::with_capacity(parts.len());
+        let max_entity_depth = max_entity_depth.unwrap_or(parts.len());
+        match self.assign_from(0, parts, max_entity_depth, accepts, &mut assigned) {
+            true => Some(assigned),
+            false => None,
+        }
+    }
+
+    fn assign_from<'s, 'a>(
+        &'s self,
+        segment: usize,
+        parts: &'a [String],
+        max_entity_depth: usize,
+        accepts: &dyn Fn(&LayoutField, &str) -> bool,
+        assigned: &mut Vec<(&'s LayoutField, &'a [String])>,
+    ) -> bool {
+        let Some(current) = self.segments.get(segment) else {
+            return parts.is_empty();
+        };
+        let max_len = match current.field {
+            LayoutField::Entity => max_entity_depth.min(parts.len()),
+            _ => 1.min(parts.len()),
+        };
+        for len in 1..=max_len {
+            let (taken, rest) = parts.split_at(len);
+            let fits = match &current.field {
+                LayoutField::Literal(name) => name == &taken[0],
+                LayoutField::Stage | LayoutField::System => accepts(&current.field, &taken[0]),
+                LayoutField::Entity | LayoutField::Named(_) => true,
+            };
+            if !fits {
+                continue;
+            }
+            assigned.push((&current.field, taken));
+            if self.assign_from(segment + 1, rest, max_entity_depth, accepts, assigned) {
+                return true;
+            }
+            assigned.pop();
+        }
+        current.optional
+            && self.assign_from(segment + 1, parts, max_entity_depth, accepts, assigned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assign(layout: &str, parts: &[&str]) -> Option<Vec<(LayoutField, String)>> {
+        assign_deep(layout, parts, Some(1))
+    }
+
+    fn assign_deep(
+        layout: &str,
+        parts: &[&str],
+        max_entity_depth: Option<usize>,
+    ) -> Option<Vec<(LayoutField, String)>> {
+        let parts: Vec<String> = parts.iter().map(|p| p.to_string()).collect();
+        let accepts = |field: &LayoutField, part: &str| match field {
+            LayoutField::Stage => part.starts_with("0"),
+            LayoutField::System => part == "pg" || part == "duck",
+            _ => true,
+        };
+        Layout::parse(layout)
+            .unwrap()
+            .assign(&parts, max_entity_depth, &accepts)
+            .map(|a| {
+                a.into_iter()
+                    .map(|(f, p)| (f.clone(), p.join("/")))
+                    .collect()
+            })
+    }
+
+    #[test]
+    fn test_parse() {
+        let layout = Layout::parse("/{domain}/{entity}/{stage?}/tasks/").unwrap();
+        assert_eq!(layout.template(), "{domain}/{entity}/{stage?}/tasks");
+        assert_eq!(layout.segments.len(), 4);
+        assert!(layout.segments[2].optional);
+        assert_eq!(
+            layout.segments[0].field,
+            LayoutField::Named("domain".to_string())
+        );
+        for bad in ["", "{stage}/{stage}", "{}", "{a-b}", "x{y}", "a//b"] {
+            assert!(Layout::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_assign() {
+        use LayoutField::*;
+        let pair = |f: LayoutField, p: &str| (f, p.to_string());
+        assert_eq!(
+            assign("{system}/{entity}/{stage}", &["pg", "orders", "01"]),
+            Some(vec![
+                pair(System, "pg"),
+                pair(Entity, "orders"),
+                pair(Stage, "01")
+            ])
+        );
+        assert_eq!(assign("{system}/{entity}/{stage}", &["orders", "01"]), None);
+        // optional segments are skipped when the folder does not fit them
+        assert_eq!(
+            assign("{system?}/{entity}/{stage}", &["orders", "01"]),
+            Some(vec![pair(Entity, "orders"), pair(Stage, "01")])
+        );
+        assert_eq!(
+            assign("{entity}/{stage}/{system?}", &["orders", "01", "duck"]),
+            Some(vec![
+                pair(Entity, "orders"),
+                pair(Stage, "01"),
+                pair(System, "duck")
+            ])
+        );
+        assert_eq!(
+            assign("etl/{entity?}", &["etl"]),
+            Some(vec![pair(Literal("etl".to_string()), "etl")])
+        );
+        assert_eq!(assign("etl/{entity?}", &["elt", "x"]), None);
+        assert_eq!(assign("{entity}", &["a", "b"]), None);
+    }
+
+    #[test]
+    fn test_assign_entity_path() {
+        use LayoutField::*;
+        let pair = |f: LayoutField, p: &str| (f, p.to_string());
+        let parts = ["finance", "billing", "01", "pg"];
+        assert_eq!(
+            assign_deep("{entity}/{stage}/{system}", &parts, None),
+            Some(vec![
+                pair(Entity, "finance/billing"),
+                pair(Stage, "01"),
+                pair(System, "pg")
+            ])
+        );
+        assert_eq!(
+            assign_deep("{entity}/{stage}/{system}", &parts, Some(1)),
+            None
+        );
+        // the entity takes as few folders as it can
+        assert_eq!(
+            assign_deep("{entity}/{stage?}", &["a", "01"], None),
+            Some(vec![pair(Entity, "a"), pair(Stage, "01")])
+        );
+    }
+}
@@ -1,20 +1,52 @@
+mod aliases;
+mod archive;
+mod change_detection;
+mod decoding;
 mod enums;
+mod errors;
+mod explain;
 mod file_classifier;
 mod file_loader;
 mod file_scanner;
+mod headers;
+mod index_cache;
+mod layout;
+mod manager_options;
 mod models;
+mod ordering;
+mod parallel;
+mod path_filter;
 mod py_utils;
+mod stages;
+mod systems;
+mod task_source;
+mod virtual_tree;
+mod watcher;
 
 use pyo3::types::{PyAny, PySet};
-use std::collections::HashMap;
-
-use crate::enums::ParamType;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::aliases::AliasRegistry;
+use crate::change_detection::{content_changed, ChangeSet, FileFingerprint};
+use crate::decoding::TextDecoder;
+use crate::enums::{ChangeKind, ErrorKind, ErrorPolicy, ParamType, SegmentRole, SortOrder};
+use crate::errors::SubtaskError;
+use crate::explain::{Explanation, SegmentMatch};
 use crate::file_classifier::FileClassifier;
-use crate::file_loader::load;
+use crate::file_loader::{check_binary, load, load_lazy, with_file_info};
+use crate::index_cache::IndexCache;
+use crate::manager_options::ManagerOptions;
 use crate::models::{RenderedSubtask, Subtask};
+use crate::stages::{CustomStage, Stage, StageArg};
+use crate::systems::{CustomSystem, System, SystemArg};
+use crate::task_source::{open_path, PySource, SharedSource};
+use crate::virtual_tree::MemoryTree;
+use crate::watcher::{events_for, FsWatch, SubtaskEvent, SubtaskEvents};
 use enums::{EtlStage, SystemType, TaskType};
-use file_scanner::FileScanner;
-use strum::IntoEnumIterator;
+use file_scanner::{FileScanner, ScanError};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
@@ -23,535 +55,1639 @@ use pyo3::PyObject;
 
 use crate::py_utils::py_path_to_string;
 
+/// How often `watch` wakes up to check for Ctrl+C and its timeout
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 // SubtaskManager with lazy loading
 #[pyclass]
 pub struct SubtaskManager {
     #[pyo3(get)]
     pub base_path: String,
-    file_paths: Vec<String>, // Store file paths instead of loaded subtasks
+    scanner: FileScanner,           // Scanner settings, kept for inspection
+    file_paths: Vec<String>,        // Store file paths instead of loaded subtasks
     subtasks: Option<Vec<Subtask>>, // Loaded lazily
-    classifier: FileClassifier, // Classifier instance for lazy loading
+    classifier: FileClassifier,     // Classifier instance for lazy loading
+    on_error: ErrorPolicy,          // What to do with files that fail to classify or load
+    errors: Vec<SubtaskError>,      // Failures recorded under ErrorPolicy::Collect
+    lazy: bool,                     // Read task files on first access instead of up front
+    fingerprints: HashMap<String, Option<FileFingerprint>>, // File state at the last load, for refresh
+    archive_prefix: Option<String>, // Folder inside an archive base_path holding the tasks
+    source: SharedSource,           // Backend the file paths are listed and read from
+    decoder: Arc<TextDecoder>,      // Turns file bytes into text
+    cache: Option<IndexCache>,      // Classified index kept on disk between runs
 }
 
-impl SubtaskManager {
-    // Internal Rust method for lazy loading (not exposed to Python)
-    fn load_subtasks(&mut self) -> PyResult<()> {
-        if self.subtasks.is_some() {
-            return Ok(()); // Already loaded
+/// Classify one file, with the size and mtime from `fingerprint`
+fn classify_file(
+    classifier: &FileClassifier,
+    decoder: &TextDecoder,
+    file_path: &str,
+    fingerprint: Option<&FileFingerprint>,
+) -> Result<Subtask, SubtaskError> {
+    let classify = || -> anyhow::Result<Subtask> {
+        let subtask = with_file_info(classifier.classify_internal(file_path)?, fingerprint);
+        // refuse oversized files by their scanned size, before anything is read
+        if let Some(fingerprint) = fingerprint {
+            decoder.check_size(file_path, fingerprint.len())?;
         }
+        Ok(subtask)
+    };
+    classify().map_err(|e| SubtaskError::from_anyhow(file_path, &e))
+}
 
-        let mut subtasks = Vec::new();
-        for file_path in &self.file_paths {
-            match self.classifier.classify_internal(file_path) {
-                Ok(s) => match load(s) {
-                    Ok(loaded) => subtasks.push(loaded),
-                    Err(e) => return Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-                },
-                Err(e) => return Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-            }
-        }
+/// A file turned into a subtask, plus its fresh classification when the index
+/// cache did not already have it
+struct BuiltSubtask {
+    classified: Option<Result<Subtask, SubtaskError>>,
+    loaded: anyhow::Result<Subtask>,
+}
 
-        self.subtasks = Some(subtasks);
-        Ok(())
+/// Classify one file, reusing the cached classification while the file is unchanged,
+/// and attach its content, read now or on first access
+fn build_subtask(
+    classifier: &FileClassifier,
+    source: &SharedSource,
+    decoder: &Arc<TextDecoder>,
+    cache: Option<&IndexCache>,
+    lazy: bool,
+    file_path: &str,
+    fingerprint: Option<&FileFingerprint>,
+) -> BuiltSubtask {
+    let (result, classified) = match cache.and_then(|c| c.lookup(file_path, fingerprint)) {
+        Some(cached) => (cached, None),
+        None => {
+            let result = classify_file(classifier, decoder, file_path, fingerprint);
+            (result.clone(), Some(result))
+        }
+    };
+    let loaded = match result {
+        Ok(subtask) if lazy => check_binary(source.as_ref(), decoder, &subtask.path)
+            .map(|()| load_lazy(subtask, source, decoder)),
+        Ok(subtask) => load(subtask, source, decoder),
+        Err(e) => Err(e.into()),
     }
+    .and_then(|subtask| classifier.read_header(subtask));
+    BuiltSubtask { classified, loaded }
 }
 
-#[pymethods]
 impl SubtaskManager {
-    #[new]
-    fn new(base_path: &Bound<'_, PyAny>) -> PyResult<Self> {
-        // Convert base_path to string, supporting both str and pathlib.Path
-        let base_path_str = py_path_to_string("base_path", base_path)?;
-
-        // Build extension list from TaskType variants
-        let extensions: Vec<String> = TaskType::iter()
-            .flat_map(|task_type| {
-                task_type
-                    .extensions()
-                    .iter()
-                    .map(|&s| s.to_string())
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        let file_scanner = FileScanner::new(extensions);
-        let file_paths = file_scanner
-            .scan_files(&base_path)
-            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    /// Manager over an in-memory map of relative path to content; nothing touches the
+    /// filesystem. `scanner` supplies extensions, filters, ordering and strictness.
+    pub fn from_memory(
+        files: HashMap<String, String>,
+        scanner: FileScanner,
+        on_error: ErrorPolicy,
+        lazy: bool,
+    ) -> anyhow::Result<Self> {
+        let tree = MemoryTree::new(files.into_iter().map(|(k, v)| (k, v.into_bytes())));
+        Self::open_source(
+            String::new(),
+            Arc::new(tree),
+            None,
+            scanner,
+            FileClassifier::default(),
+            on_error,
+            lazy,
+            Arc::default(),
+        )
+    }
 
-        // Create classifier instance for lazy loading
-        let classifier = FileClassifier {
-            base_path: base_path_str.clone(),
-        };
+    /// Manager over any task source; `scanner` supplies extensions, filters, ordering
+    /// and strictness, `classifier` the classification settings (its base path is
+    /// replaced by the root of the source) and `decoder` how file bytes become text
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_source(
+        py: Python<'_>,
+        base_path: String,
+        source: SharedSource,
+        archive_prefix: Option<String>,
+        scanner: FileScanner,
+        classifier: FileClassifier,
+        on_error: ErrorPolicy,
+        lazy: bool,
+        decoder: Arc<TextDecoder>,
+    ) -> PyResult<Self> {
+        py.allow_threads(|| {
+            Self::open_source(
+                base_path,
+                source,
+                archive_prefix,
+                scanner,
+                classifier,
+                on_error,
+                lazy,
+                decoder,
+            )
+        })
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("{e:#}")))
+    }
 
+    // `with_source` for callers that do not hold the GIL
+    #[allow(clippy::too_many_arguments)]
+    fn open_source(
+        base_path: String,
+        source: SharedSource,
+        archive_prefix: Option<String>,
+        mut scanner: FileScanner,
+        classifier: FileClassifier,
+        on_error: ErrorPolicy,
+        lazy: bool,
+        decoder: Arc<TextDecoder>,
+    ) -> anyhow::Result<Self> {
+        // paths are classified relative to the root of the source
+        let classifier = classifier.with_base_path(&source.root());
+        let file_paths = scanner.scan_checked(source.as_ref(), &classifier)?;
         Ok(SubtaskManager {
-            base_path: base_path_str,
+            base_path,
+            scanner,
             file_paths,
-            subtasks: None, // Not loaded yet
+            subtasks: None,
             classifier,
+            on_error,
+            errors: Vec::new(),
+            lazy,
+            fingerprints: HashMap::new(),
+            archive_prefix,
+            source,
+            decoder,
+            cache: None,
         })
     }
 
-    /// Getter for subtasks that loads them if needed
-    #[getter]
-    fn subtasks(&mut self, py: Python) -> PyResult<Py<PyList>> {
-        self.load_subtasks()?;
-
-        let subtasks = self.subtasks.as_ref().unwrap();
-        let py_list = PyList::empty_bound(py);
+    /// Keep the classified index in the cache file at `path`, reusing what a previous
+    /// run stored there for files that have not changed since
+    pub fn use_cache(&mut self, path: &Path) {
+        // a cache written with other classification settings is of no use
+        let key = format!(
+            "{:?};max_file_size={:?}",
+            self.classifier,
+            self.decoder.max_file_size()
+        );
+        self.cache = Some(IndexCache::load(path, &key));
+    }
 
-        for subtask in subtasks {
-            // Since Subtask is a pyclass, wrap it in Py
-            let py_subtask = Py::new(py, subtask.clone())?;
-            py_list.append(py_subtask)?;
+    // Record fresh classifications in the index cache and write it back if it changed.
+    // The cache only saves work, so failing to write it does not fail the load.
+    fn update_cache(
+        &mut self,
+        classified: Vec<(
+            String,
+            Option<FileFingerprint>,
+            Result<Subtask, SubtaskError>,
+        )>,
+    ) {
+        let Some(cache) = self.cache.as_mut() else {
+            return;
+        };
+        for (file_path, fingerprint, result) in classified {
+            cache.insert(&file_path, fingerprint, result);
         }
+        cache.retain_paths(&self.file_paths);
+        let _ = cache.save();
+    }
 
-        Ok(py_list.into())
+    // Scan the task source again, reopening it first if it has to be (archives)
+    fn rescan(&mut self, py: Python<'_>) -> PyResult<(Vec<String>, SharedSource)> {
+        let source = py
+            .allow_threads(|| self.source.reload())
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("{e:#}")))?
+            .unwrap_or_else(|| self.source.clone());
+        let found = self
+            .scanner
+            .scan_source(py, source.as_ref(), &self.classifier)?;
+        Ok((found, source))
     }
 
-    // Get file paths as Python list
-    #[getter]
-    fn file_paths(&self, py: Python) -> PyResult<Py<PyList>> {
-        let py_list = PyList::empty_bound(py);
+    /// Apply the on_error policy to a file that failed to classify or load
+    fn handle_error(
+        &self,
+        errors: &mut Vec<SubtaskError>,
+        file_path: &str,
+        e: &anyhow::Error,
+    ) -> PyResult<()> {
+        match self.on_error {
+            ErrorPolicy::Raise => {
+                let error = SubtaskError::from_anyhow(file_path, e);
+                Err(pyo3::exceptions::PyRuntimeError::new_err(error.__str__()))
+            }
+            ErrorPolicy::Skip => Ok(()),
+            ErrorPolicy::Collect => {
+                errors.push(SubtaskError::from_anyhow(file_path, e));
+                Ok(())
+            }
+        }
+    }
+
+    // Internal Rust method for lazy loading (not exposed to Python).
+    // Classification is eager; file contents are only read here when `lazy` is off.
+    fn load_subtasks(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.subtasks.is_some() {
+            return Ok(()); // Already loaded
+        }
 
-        for file_path in &self.file_paths {
-            py_list.append(file_path)?;
+        // Classify (and read, if not lazy) on worker threads without holding the GIL;
+        // results come back in file_paths order. The file is stat-ed before it is read,
+        // so an edit made meanwhile is still picked up by the next refresh.
+        let classifier = &self.classifier;
+        let source = &self.source;
+        let decoder = &self.decoder;
+        let cache = self.cache.as_ref();
+        let lazy = self.lazy;
+        let results = py.allow_threads(|| {
+            self.scanner
+                .workers()
+                .ordered_map(&self.file_paths, |file_path| {
+                    let fingerprint = source.stat(file_path);
+                    let built = build_subtask(
+                        classifier,
+                        source,
+                        decoder,
+                        cache,
+                        lazy,
+                        file_path,
+                        fingerprint.as_ref(),
+                    );
+                    (fingerprint, built)
+                })
+        });
+
+        let mut subtasks = Vec::with_capacity(results.len());
+        let mut errors = Vec::new();
+        let mut fingerprints = HashMap::with_capacity(results.len());
+        let mut classified = Vec::new();
+        for (file_path, (fingerprint, built)) in self.file_paths.iter().zip(results) {
+            if let Some(result) = built.classified {
+                classified.push((file_path.clone(), fingerprint.clone(), result));
+            }
+            fingerprints.insert(file_path.clone(), fingerprint);
+            match built.loaded {
+                Ok(loaded) => subtasks.push(loaded),
+                Err(e) => self.handle_error(&mut errors, file_path, &e)?,
+            }
         }
+        self.errors = errors;
+        self.fingerprints = fingerprints;
 
-        Ok(py_list.into())
+        self.subtasks = Some(subtasks);
+        self.update_cache(classified);
+        Ok(())
     }
 
-    // Get the number of subtasks (without loading them)
-    #[getter]
-    fn num_files(&self) -> usize {
-        self.file_paths.len()
+    // Events for a change set applied by refresh, using the reloaded subtasks and errors
+    fn events_for_changes(&self, changes: &ChangeSet) -> Vec<SubtaskEvent> {
+        let subtasks = self.subtasks.as_deref().unwrap_or_default();
+        events_for(changes, |path| {
+            if let Some(subtask) = subtasks.iter().find(|s| s.path == path) {
+                return Ok(subtask.clone());
+            }
+            Err(self
+                .errors
+                .iter()
+                .find(|e| e.path == path)
+                .cloned()
+                .unwrap_or_else(|| {
+                    SubtaskError::new(path, ErrorKind::Other, "File was skipped by on_error")
+                }))
+        })
     }
 
-    // Get the classifier instance
-    #[getter]
-    fn classifier(&self) -> FileClassifier {
-        self.classifier.clone()
+    /// Rust-side counterpart of `watch`: a blocking iterator over batches of classified
+    /// changes, starting from the files present now
+    pub fn events(&self, debounce: Duration) -> notify::Result<SubtaskEvents> {
+        SubtaskEvents::new(
+            self.scanner.clone(),
+            self.classifier.clone(),
+            self.source.clone(),
+            self.decoder.clone(),
+            debounce,
+        )
     }
 
-    // Explicit method to load subtasks
-    fn load_all(&mut self) -> PyResult<()> {
-        self.load_subtasks()
+    // Read the content of every subtask that has not been read yet
+    fn load_contents(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.load_subtasks(py)?;
+
+        let subtasks = self.subtasks.as_ref().unwrap();
+        let results = py.allow_threads(|| {
+            self.scanner
+                .workers()
+                .ordered_map(subtasks, |s| s.load_command().err())
+        });
+
+        let mut errors = Vec::new();
+        let mut failed = Vec::new();
+        for (subtask, result) in subtasks.iter().zip(results) {
+            if let Some(e) = result {
+                self.handle_error(&mut errors, &subtask.path, &e)?;
+                failed.push(subtask.path.clone());
+            }
+        }
+
+        if !failed.is_empty() {
+            if let Some(subtasks) = self.subtasks.as_mut() {
+                subtasks.retain(|s| !failed.contains(&s.path));
+            }
+        }
+        self.errors.extend(errors);
+        Ok(())
     }
+}
 
-    #[pyo3(signature = (etl_stage=None, entity=None, system_type=None, task_type=None, is_common=None, include_common=None))]
-    fn get_tasks(
-        &mut self,
-        py: Python,
-        etl_stage: Option<EtlStage>,
-        entity: Option<String>,
-        system_type: Option<SystemType>,
-        task_type: Option<TaskType>,
-        is_common: Option<bool>,
-        include_common: Option<bool>,
-    ) -> PyResult<Py<PyList>> {
-        // Ensure subtasks are loaded before filtering
-        self.load_subtasks()?;
-
-        let include_common = include_common.unwrap_or(true);
-        let mut filtered: Vec<crate::models::Subtask> = Vec::new();
-
-        for subtask in self.subtasks.as_ref().unwrap() {
-            if let Some(ref es) = etl_stage {
-                if subtask.stage.as_ref() != Some(es) {
-                    continue;
-                }
+// The wrappers `#[pymethods]` generates convert every returned `PyResult` into itself,
+// and pyo3 0.22 passes only `cfg` attributes on to them; the allow goes on a const block
+// around each impl with such methods instead
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl SubtaskManager {
+        #[new]
+        #[pyo3(signature = (base_path, respect_ignore_files=true, include=None, exclude=None, sort_by=SortOrder::Path, strict=false, follow_symlinks=false, include_hidden=true, max_depth=None, threads=None, on_error=ErrorPolicy::Raise, lazy=true, archive_prefix=None, fallback_encodings=None, normalize_newlines=false, max_file_size=None, detect_binary=true, layout=None, max_entity_depth=Some(1), aliases=None, cache_path=None, strict_classification=false, read_headers=false))]
+        #[allow(clippy::too_many_arguments)]
+        fn new(
+            py: Python<'_>,
+            base_path: &Bound<'_, PyAny>,
+            respect_ignore_files: bool,
+            include: Option<Vec<String>>,
+            exclude: Option<Vec<String>>,
+            sort_by: SortOrder,
+            strict: bool,
+            follow_symlinks: bool,
+            include_hidden: bool,
+            max_depth: Option<usize>,
+            threads: Option<usize>,
+            on_error: ErrorPolicy,
+            lazy: bool,
+            archive_prefix: Option<String>,
+            fallback_encodings: Option<Vec<String>>,
+            normalize_newlines: bool,
+            max_file_size: Option<u64>,
+            detect_binary: bool,
+            layout: Option<&str>,
+            max_entity_depth: Option<usize>,
+            aliases: Option<&Bound<'_, PyAny>>,
+            cache_path: Option<&Bound<'_, PyAny>>,
+            strict_classification: bool,
+            read_headers: bool,
+        ) -> PyResult<Self> {
+            // Convert base_path to string, supporting both str and pathlib.Path
+            let base_path_str = py_path_to_string("base_path", base_path)?;
+
+            let (file_scanner, classifier, decoder) = ManagerOptions {
+                respect_ignore_files,
+                include,
+                exclude,
+                sort_by,
+                strict,
+                follow_symlinks,
+                include_hidden,
+                max_depth,
+                threads,
+                fallback_encodings,
+                normalize_newlines,
+                max_file_size,
+                detect_binary,
+                layout,
+                max_entity_depth,
+                aliases,
+                strict_classification,
+                read_headers,
             }
-            if let Some(ref en) = entity {
-                if subtask.entity.as_ref() != Some(en) {
-                    continue;
-                }
+            .build_scanner_and_classifier()?;
+            let cache_path = cache_path
+                .map(|path| py_path_to_string("cache_path", path))
+                .transpose()?;
+            let source = open_path(
+                py,
+                &base_path_str,
+                archive_prefix.as_deref(),
+                &file_scanner,
+                decoder.max_file_size(),
+            )?;
+            let mut manager = Self::with_source(
+                py,
+                base_path_str,
+                source,
+                archive_prefix,
+                file_scanner,
+                classifier,
+                on_error,
+                lazy,
+                decoder,
+            )?;
+            if let Some(cache_path) = cache_path {
+                manager.use_cache(Path::new(&cache_path));
             }
-            if let Some(ref st) = system_type {
-                if subtask.system_type.as_ref() != Some(st) {
-                    continue;
-                }
+            Ok(manager)
+        }
+
+        /// Build a manager from a mapping of relative path to content (`str` or `bytes`);
+        /// classification and filtering work as for a directory, without touching the filesystem
+        #[staticmethod]
+        #[pyo3(signature = (files, respect_ignore_files=true, include=None, exclude=None, sort_by=SortOrder::Path, strict=false, include_hidden=true, max_depth=None, threads=None, on_error=ErrorPolicy::Raise, lazy=true, fallback_encodings=None, normalize_newlines=false, max_file_size=None, detect_binary=true, layout=None, max_entity_depth=Some(1), aliases=None, strict_classification=false, read_headers=false))]
+        #[allow(clippy::too_many_arguments)]
+        fn from_dict(
+            py: Python<'_>,
+            files: &Bound<'_, PyDict>,
+            respect_ignore_files: bool,
+            include: Option<Vec<String>>,
+            exclude: Option<Vec<String>>,
+            sort_by: SortOrder,
+            strict: bool,
+            include_hidden: bool,
+            max_depth: Option<usize>,
+            threads: Option<usize>,
+            on_error: ErrorPolicy,
+            lazy: bool,
+            fallback_encodings: Option<Vec<String>>,
+            normalize_newlines: bool,
+            max_file_size: Option<u64>,
+            detect_binary: bool,
+            layout: Option<&str>,
+            max_entity_depth: Option<usize>,
+            aliases: Option<&Bound<'_, PyAny>>,
+            strict_classification: bool,
+            read_headers: bool,
+        ) -> PyResult<Self> {
+            let mut entries = Vec::with_capacity(files.len());
+            for (key, value) in files.iter() {
+                let path = py_path_to_string("files key", &key)?;
+                let data = match value.extract::<String>() {
+                    Ok(text) => text.into_bytes(),
+                    Err(_) => value.extract::<Vec<u8>>().map_err(|_| {
+                        PyValueError::new_err(format!("Content of '{path}' must be str or bytes"))
+                    })?,
+                };
+                entries.push((path, data));
+            }
+            let (scanner, classifier, decoder) = ManagerOptions {
+                respect_ignore_files,
+                include,
+                exclude,
+                sort_by,
+                strict,
+                follow_symlinks: false,
+                include_hidden,
+                max_depth,
+                threads,
+                fallback_encodings,
+                normalize_newlines,
+                max_file_size,
+                detect_binary,
+                layout,
+                max_entity_depth,
+                aliases,
+                strict_classification,
+                read_headers,
             }
-            if let Some(ref tt) = task_type {
-                if subtask.task_type.as_ref() != Some(tt) {
-                    continue;
+            .build_scanner_and_classifier()?;
+            Self::with_source(
+                py,
+                String::new(),
+                Arc::new(MemoryTree::new(entries)),
+                None,
+                scanner,
+                classifier,
+                on_error,
+                lazy,
+                decoder,
+            )
+        }
+
+        /// Build a manager over a custom task source: any object with `list()` returning the
+        /// file paths and `read(path)` returning their content as `str` or `bytes`.
+        /// An optional `stat(path)` returning `(size, mtime)` makes `refresh` cheaper, and an
+        /// optional `root` attribute names the folder the task layout starts in.
+        #[staticmethod]
+        #[pyo3(signature = (source, respect_ignore_files=true, include=None, exclude=None, sort_by=SortOrder::Path, strict=false, include_hidden=true, max_depth=None, threads=None, on_error=ErrorPolicy::Raise, lazy=true, fallback_encodings=None, normalize_newlines=false, max_file_size=None, detect_binary=true, layout=None, max_entity_depth=Some(1), aliases=None, strict_classification=false, read_headers=false))]
+        #[allow(clippy::too_many_arguments)]
+        fn from_source(
+            py: Python<'_>,
+            source: &Bound<'_, PyAny>,
+            respect_ignore_files: bool,
+            include: Option<Vec<String>>,
+            exclude: Option<Vec<String>>,
+            sort_by: SortOrder,
+            strict: bool,
+            include_hidden: bool,
+            max_depth: Option<usize>,
+            threads: Option<usize>,
+            on_error: ErrorPolicy,
+            lazy: bool,
+            fallback_encodings: Option<Vec<String>>,
+            normalize_newlines: bool,
+            max_file_size: Option<u64>,
+            detect_binary: bool,
+            layout: Option<&str>,
+            max_entity_depth: Option<usize>,
+            aliases: Option<&Bound<'_, PyAny>>,
+            strict_classification: bool,
+            read_headers: bool,
+        ) -> PyResult<Self> {
+            for method in ["list", "read"] {
+                if !source.getattr(method).is_ok_and(|m| m.is_callable()) {
+                    return Err(PyValueError::new_err(format!(
+                        "Task source must have a callable '{method}' method"
+                    )));
                 }
             }
-            if let Some(ic) = is_common {
-                if subtask.is_common != ic {
-                    continue;
+            let (scanner, classifier, decoder) = ManagerOptions {
+                respect_ignore_files,
+                include,
+                exclude,
+                sort_by,
+                strict,
+                follow_symlinks: false,
+                include_hidden,
+                max_depth,
+                threads,
+                fallback_encodings,
+                normalize_newlines,
+                max_file_size,
+                detect_binary,
+                layout,
+                max_entity_depth,
+                aliases,
+                strict_classification,
+                read_headers,
+            }
+            .build_scanner_and_classifier()?;
+            let source: SharedSource = Arc::new(PySource::new(source.clone().unbind()));
+            Self::with_source(
+                py,
+                String::new(),
+                source,
+                None,
+                scanner,
+                classifier,
+                on_error,
+                lazy,
+                decoder,
+            )
+        }
+
+        /// Getter for subtasks that loads them if needed
+        #[getter]
+        fn subtasks(&mut self, py: Python) -> PyResult<Py<PyList>> {
+            self.load_subtasks(py)?;
+
+            let subtasks = self.subtasks.as_ref().unwrap();
+            let py_list = PyList::empty_bound(py);
+
+            for subtask in subtasks {
+                // Since Subtask is a pyclass, wrap it in Py
+                let py_subtask = Py::new(py, subtask.clone())?;
+                py_list.append(py_subtask)?;
+            }
+
+            Ok(py_list.into())
+        }
+
+        // Get file paths as Python list
+        #[getter]
+        fn file_paths(&self, py: Python) -> PyResult<Py<PyList>> {
+            let py_list = PyList::empty_bound(py);
+
+            for file_path in &self.file_paths {
+                py_list.append(file_path)?;
+            }
+
+            Ok(py_list.into())
+        }
+
+        // Get the number of subtasks (without loading them)
+        #[getter]
+        fn num_files(&self) -> usize {
+            self.file_paths.len()
+        }
+
+        // Ordering applied to file_paths and subtasks
+        #[getter]
+        fn sort_by(&self) -> SortOrder {
+            self.scanner.sort_by()
+        }
+
+        // Include globs used while scanning
+        #[getter]
+        fn include(&self) -> Vec<String> {
+            self.scanner.include()
+        }
+
+        // Exclude globs used while scanning
+        #[getter]
+        fn exclude(&self) -> Vec<String> {
+            self.scanner.exclude()
+        }
+
+        // Paths skipped while scanning, with the reason
+        #[getter]
+        fn scan_errors(&self) -> Vec<ScanError> {
+            self.scanner.scan_errors()
+        }
+
+        #[getter]
+        fn strict(&self) -> bool {
+            self.scanner.strict()
+        }
+
+        #[getter]
+        fn follow_symlinks(&self) -> bool {
+            self.scanner.follow_symlinks()
+        }
+
+        #[getter]
+        fn include_hidden(&self) -> bool {
+            self.scanner.include_hidden()
+        }
+
+        #[getter]
+        fn max_depth(&self) -> Option<usize> {
+            self.scanner.max_depth()
+        }
+
+        #[getter]
+        fn threads(&self) -> Option<usize> {
+            self.scanner.threads()
+        }
+
+        #[getter]
+        fn on_error(&self) -> ErrorPolicy {
+            self.on_error
+        }
+
+        #[getter]
+        fn lazy(&self) -> bool {
+            self.lazy
+        }
+
+        /// Encodings tried, in order, for files that are not valid UTF-8
+        #[getter]
+        fn fallback_encodings(&self) -> Vec<String> {
+            self.decoder.fallback_encodings()
+        }
+
+        /// Whether `\r\n` and `\r` line endings are converted to `\n` when files are read
+        #[getter]
+        fn normalize_newlines(&self) -> bool {
+            self.decoder.normalize_newlines()
+        }
+
+        /// Files larger than this many bytes fail with ErrorKind.TooLarge instead of being read
+        #[getter]
+        fn max_file_size(&self) -> Option<u64> {
+            self.decoder.max_file_size()
+        }
+
+        /// Whether files containing NUL bytes fail with ErrorKind.Binary
+        #[getter]
+        fn detect_binary(&self) -> bool {
+            self.decoder.detect_binary()
+        }
+
+        /// File the classified index is cached in between runs, if any
+        #[getter]
+        fn cache_path(&self) -> Option<String> {
+            self.cache
+                .as_ref()
+                .map(|cache| cache.path().to_string_lossy().into_owned())
+        }
+
+        /// Folder inside the archive that holds the tasks, when base_path is an archive
+        #[getter]
+        fn archive_prefix(&self) -> Option<String> {
+            self.archive_prefix.clone()
+        }
+
+        /// Files that failed to classify or load; filled under ErrorPolicy::Collect.
+        /// With lazy loading, read errors show up once contents are read by `load_all`.
+        #[getter]
+        fn errors(&mut self, py: Python) -> PyResult<Vec<SubtaskError>> {
+            self.load_subtasks(py)?;
+            Ok(self.errors.clone())
+        }
+
+        /// Ambiguous folders of the loaded subtasks (`ErrorKind.Ambiguous`), e.g. two folders
+        /// naming a system or an entity named like a stage; under `strict_classification`
+        /// such files fail instead and this list stays empty
+        #[getter]
+        fn classification_warnings(&mut self, py: Python) -> PyResult<Vec<SubtaskError>> {
+            self.load_subtasks(py)?;
+            Ok(self
+                .subtasks
+                .iter()
+                .flatten()
+                .flat_map(|subtask| self.classifier.warnings(&subtask.path))
+                .collect())
+        }
+
+        /// Whether the header at the top of each task file is applied when it is loaded
+        #[getter]
+        fn read_headers(&self) -> bool {
+            self.classifier.reads_headers()
+        }
+
+        // Get the classifier instance
+        #[getter]
+        fn classifier(&self) -> FileClassifier {
+            self.classifier.clone()
+        }
+
+        // Explicit method to load subtasks together with their file contents
+        fn load_all(&mut self, py: Python<'_>) -> PyResult<()> {
+            self.load_contents(py)
+        }
+
+        /// Rescan the base directory and reload only the files added or modified since the last load.
+        /// Files whose size or modification time changed but whose already-read content did not
+        /// are not reported. Before the first load only added and removed paths are reported.
+        fn refresh(&mut self, py: Python<'_>) -> PyResult<ChangeSet> {
+            let (new_paths, source) = self.rescan(py)?;
+
+            let Some(old_subtasks) = self.subtasks.as_ref() else {
+                let changes = ChangeSet::between_paths(&self.file_paths, &new_paths);
+                self.file_paths = new_paths;
+                self.source = source;
+                return Ok(changes);
+            };
+
+            let workers = self.scanner.workers().clone();
+            let decoder = &self.decoder;
+            let fingerprints = py
+                .allow_threads(|| workers.ordered_map(&new_paths, |p| (p.clone(), source.stat(p))));
+            let mut changes =
+                ChangeSet::detect(&self.file_paths, &self.fingerprints, &fingerprints);
+
+            // a touched file with unchanged text is not a modification
+            let old_by_path: HashMap<&str, &Subtask> =
+                old_subtasks.iter().map(|s| (s.path.as_str(), s)).collect();
+            let still_modified = py.allow_threads(|| {
+                workers.ordered_map(&changes.modified, |p| {
+                    old_by_path
+                        .get(p.as_str())
+                        .is_none_or(|s| content_changed(s, source.as_ref(), decoder))
+                })
+            });
+            let mut flags = still_modified.into_iter();
+            changes.modified.retain(|_| flags.next().unwrap_or(true));
+
+            let to_load: Vec<String> = changes
+                .added
+                .iter()
+                .chain(&changes.modified)
+                .cloned()
+                .collect();
+            let classifier = &self.classifier;
+            let cache = self.cache.as_ref();
+            let lazy = self.lazy;
+            let fingerprint_of: HashMap<&str, Option<&FileFingerprint>> = fingerprints
+                .iter()
+                .map(|(p, f)| (p.as_str(), f.as_ref()))
+                .collect();
+            let results = py.allow_threads(|| {
+                workers.ordered_map(&to_load, |p| {
+                    let fingerprint = fingerprint_of.get(p.as_str()).copied().flatten();
+                    build_subtask(classifier, &source, decoder, cache, lazy, p, fingerprint)
+                })
+            });
+
+            // keep errors of untouched files, replace those of reloaded ones
+            let current: HashSet<&str> = new_paths.iter().map(|p| p.as_str()).collect();
+            let reloaded_paths: HashSet<&str> = to_load.iter().map(|p| p.as_str()).collect();
+            let mut errors: Vec<SubtaskError> = self
+                .errors
+                .iter()
+                .filter(|e| {
+                    current.contains(e.path.as_str()) && !reloaded_paths.contains(e.path.as_str())
+                })
+                .cloned()
+                .collect();
+            let mut reloaded: HashMap<&str, Subtask> = HashMap::new();
+            let mut classified = Vec::new();
+            for (file_path, built) in to_load.iter().zip(results) {
+                if let Some(result) = built.classified {
+                    let fingerprint = fingerprint_of.get(file_path.as_str()).copied().flatten();
+                    classified.push((file_path.clone(), fingerprint.cloned(), result));
+                }
+                match built.loaded {
+                    Ok(loaded) => {
+                        reloaded.insert(file_path.as_str(), loaded);
+                    }
+                    Err(e) => self.handle_error(&mut errors, file_path, &e)?,
                 }
             }
-            filtered.push(subtask.clone());
+
+            let subtasks: Vec<Subtask> = new_paths
+                .iter()
+                .filter_map(|p| {
+                    reloaded.remove(p.as_str()).or_else(|| {
+                        // touched files with unchanged text still get the new size and mtime
+                        let fingerprint = fingerprint_of.get(p.as_str()).copied().flatten();
+                        let old = old_by_path.get(p.as_str())?;
+                        Some(with_file_info((*old).clone(), fingerprint))
+                    })
+                })
+                .collect();
+
+            self.subtasks = Some(subtasks);
+            self.errors = errors;
+            self.fingerprints = fingerprints.into_iter().collect();
+            self.file_paths = new_paths;
+            self.source = source;
+            self.update_cache(classified);
+            Ok(changes)
         }
 
-        if include_common {
-            for s in self.subtasks.as_ref().unwrap() {
-                if s.is_common && !filtered.iter().any(|x| x.path == s.path) {
-                    filtered.push(s.clone());
+        /// Watch the base directory and call `callback(events)` with each debounced batch of
+        /// changes until it returns False or `timeout` seconds have passed.
+        /// The manager is refreshed before every call, so `subtasks` already reflects the batch.
+        #[pyo3(signature = (callback, debounce_ms=200, timeout=None))]
+        fn watch(
+            &mut self,
+            py: Python<'_>,
+            callback: PyObject,
+            debounce_ms: u64,
+            timeout: Option<f64>,
+        ) -> PyResult<()> {
+            let Some(watch_path) = self.source.watch_path().map(|p| p.to_string()) else {
+                return Err(PyValueError::new_err(
+                    "This task source has no files on disk to watch",
+                ));
+            };
+            self.load_subtasks(py)?;
+            let mut watch =
+                FsWatch::new(&watch_path, Duration::from_millis(debounce_ms)).map_err(|e| {
+                    pyo3::exceptions::PyRuntimeError::new_err(format!("Cannot watch: {e}"))
+                })?;
+            // catch up with edits made between the last load and the start of the watch
+            let mut pending = true;
+            let deadline = timeout.map(|t| Instant::now() + Duration::from_secs_f64(t.max(0.0)));
+
+            loop {
+                if pending {
+                    let changes = self.refresh(py)?;
+                    if !changes.is_empty() {
+                        let events = self.events_for_changes(&changes);
+                        let result = callback.call1(py, (events,))?;
+                        if let Ok(false) = result.extract::<bool>(py) {
+                            return Ok(());
+                        }
+                    }
+                }
+
+                // wake up regularly so Ctrl+C and the timeout are honoured
+                let mut wait = WATCH_POLL_INTERVAL;
+                if let Some(deadline) = deadline {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Ok(());
+                    }
+                    wait = wait.min(left);
                 }
+                // Receiver is Send but not Sync, so hand the watch to the GIL-free closure by &mut
+                let watch = &mut watch;
+                match py.allow_threads(move || watch.wait(Some(wait))) {
+                    Some(changed) => pending = changed,
+                    None => return Ok(()),
+                }
+                py.check_signals()?;
             }
         }
 
-        // Convert filtered results to Python list
-        let py_list = PyList::empty_bound(py);
-        for subtask in filtered {
-            let py_subtask = Py::new(py, subtask)?;
-            py_list.append(py_subtask)?;
+        /// Drop lazily read file contents of every subtask; they are read again on next access
+        fn release_contents(&self) {
+            for subtask in self.subtasks.iter().flatten() {
+                subtask.release();
+            }
         }
 
-        Ok(py_list.into())
-    }
+        /// Subtasks matching every given filter; `entity` must match exactly, while
+        /// `entity_prefix` (`"finance"` or `"finance/billing"`) matches whole leading folders
+        #[pyo3(signature = (etl_stage=None, entity=None, system_type=None, task_type=None, is_common=None, include_common=None, entity_prefix=None))]
+        #[allow(clippy::too_many_arguments)]
+        fn get_tasks(
+            &mut self,
+            py: Python,
+            etl_stage: Option<StageArg>,
+            entity: Option<String>,
+            system_type: Option<SystemArg>,
+            task_type: Option<TaskType>,
+            is_common: Option<bool>,
+            include_common: Option<bool>,
+            entity_prefix: Option<String>,
+        ) -> PyResult<Py<PyList>> {
+            // Ensure subtasks are loaded before filtering
+            self.load_subtasks(py)?;
+
+            let include_common = include_common.unwrap_or(true);
+            let aliases = self.classifier.alias_registry();
+            let mut filtered: Vec<crate::models::Subtask> = Vec::new();
+
+            for subtask in self.subtasks.as_ref().unwrap() {
+                if let Some(ref es) = etl_stage {
+                    if !subtask
+                        .pipeline_stage()
+                        .is_some_and(|stage| stage.matches(es, aliases))
+                    {
+                        continue;
+                    }
+                }
+                if let Some(ref en) = entity {
+                    if subtask.entity.as_ref() != Some(en) {
+                        continue;
+                    }
+                }
+                if let Some(ref prefix) = entity_prefix {
+                    if !subtask.entity_starts_with(prefix) {
+                        continue;
+                    }
+                }
+                if let Some(ref st) = system_type {
+                    if !subtask
+                        .system()
+                        .is_some_and(|system| system.matches(st, aliases))
+                    {
+                        continue;
+                    }
+                }
+                if let Some(ref tt) = task_type {
+                    if subtask.task_type.as_ref() != Some(tt) {
+                        continue;
+                    }
+                }
+                if let Some(ic) = is_common {
+                    if subtask.is_common != ic {
+                        continue;
+                    }
+                }
+                filtered.push(subtask.clone());
+            }
+
+            if include_common {
+                for s in self.subtasks.as_ref().unwrap() {
+                    if s.is_common && !filtered.iter().any(|x| x.path == s.path) {
+                        filtered.push(s.clone());
+                    }
+                }
+            }
+
+            // Convert filtered results to Python list
+            let py_list = PyList::empty_bound(py);
+            for subtask in filtered {
+                let py_subtask = Py::new(py, subtask)?;
+                py_list.append(py_subtask)?;
+            }
+
+            Ok(py_list.into())
+        }
 
-    #[pyo3(signature = (name, entity=None))]
-    fn get_task(&mut self, py: Python, name: String, entity: Option<String>) -> PyResult<PyObject> {
-        // Ensure subtasks are loaded before searching
-        self.load_subtasks()?;
+        #[pyo3(signature = (name, entity=None))]
+        fn get_task(
+            &mut self,
+            py: Python,
+            name: String,
+            entity: Option<String>,
+        ) -> PyResult<PyObject> {
+            // Ensure subtasks are loaded before searching
+            self.load_subtasks(py)?;
 
-        for s in self.subtasks.as_ref().unwrap() {
-            if s.name == name {
-                if let Some(ref e) = entity {
-                    if s.entity.as_ref() == Some(e) {
+            for s in self.subtasks.as_ref().unwrap() {
+                if s.name == name {
+                    if let Some(ref e) = entity {
+                        if s.entity.as_ref() == Some(e) {
+                            let py_sub = Py::new(py, s.clone()).map_err(|e| {
+                                pyo3::exceptions::PyRuntimeError::new_err(e.to_string())
+                            })?;
+                            return Ok(py_sub.into_py(py));
+                        }
+                    } else {
                         let py_sub = Py::new(py, s.clone()).map_err(|e| {
                             pyo3::exceptions::PyRuntimeError::new_err(e.to_string())
                         })?;
                         return Ok(py_sub.into_py(py));
                     }
-                } else {
-                    let py_sub = Py::new(py, s.clone())
-                        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
-                    return Ok(py_sub.into_py(py));
                 }
             }
+            Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Task with name '{}' not found",
+                name
+            )))
         }
-        Err(pyo3::exceptions::PyValueError::new_err(format!(
-            "Task with name '{}' not found",
-            name
-        )))
     }
-}
+};
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl ParamType {
+        pub fn __str__(&self) -> &'static str {
+            self.name()
+        }
+        pub fn __repr__(&self) -> String {
+            format!("ParamType.{}", self.name().to_uppercase())
+        }
+        #[getter]
+        #[pyo3(name = "name")]
+        fn param_type_name_py(&self) -> &'static str {
+            self.name()
+        }
+        #[getter]
+        #[pyo3(name = "aliases")]
+        fn param_type_aliases_py(&self) -> Vec<&'static str> {
+            self.aliases().to_vec()
+        }
+        #[getter]
+        #[pyo3(name = "id")]
+        fn param_type_id_py(&self) -> u8 {
+            self.id()
+        }
 
-#[pymethods]
-impl ParamType {
-    pub fn __str__(&self) -> &'static str {
-        self.name()
-    }
-    pub fn __repr__(&self) -> String {
-        format!("ParamType.{}", self.name().to_uppercase())
-    }
-    #[getter]
-    #[pyo3(name = "name")]
-    fn param_type_name_py(&self) -> &'static str {
-        self.name()
-    }
-    #[getter]
-    #[pyo3(name = "aliases")]
-    fn param_type_aliases_py(&self) -> Vec<&'static str> {
-        self.aliases().to_vec()
+        #[staticmethod]
+        #[pyo3(name = "from_alias")]
+        fn from_alias_py(alias: String) -> PyResult<ParamType> {
+            ParamType::from_alias(&alias).map_err(PyValueError::new_err)
+        }
     }
-    #[getter]
-    #[pyo3(name = "id")]
-    fn param_type_id_py(&self) -> u8 {
-        self.id()
+};
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl SortOrder {
+        pub fn __str__(&self) -> &'static str {
+            self.name()
+        }
+
+        pub fn __repr__(&self) -> String {
+            format!("SortOrder.{}", self.name().to_uppercase())
+        }
+
+        #[getter]
+        #[pyo3(name = "id")]
+        fn sort_order_id_py(&self) -> u8 {
+            self.id()
+        }
+
+        #[getter]
+        #[pyo3(name = "name")]
+        fn sort_order_name_py(&self) -> &'static str {
+            self.name()
+        }
+        #[getter]
+        #[pyo3(name = "aliases")]
+        fn sort_order_aliases_py(&self) -> Vec<&'static str> {
+            self.aliases().to_vec()
+        }
+
+        #[staticmethod]
+        #[pyo3(name = "from_alias")]
+        fn from_alias_py(alias: String) -> PyResult<SortOrder> {
+            SortOrder::from_alias(&alias).map_err(PyValueError::new_err)
+        }
     }
+};
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl ErrorPolicy {
+        pub fn __str__(&self) -> &'static str {
+            self.name()
+        }
+
+        pub fn __repr__(&self) -> String {
+            format!("ErrorPolicy.{}", self.name().to_uppercase())
+        }
+
+        #[getter]
+        #[pyo3(name = "id")]
+        fn error_policy_id_py(&self) -> u8 {
+            self.id()
+        }
+
+        #[getter]
+        #[pyo3(name = "name")]
+        fn error_policy_name_py(&self) -> &'static str {
+            self.name()
+        }
+        #[getter]
+        #[pyo3(name = "aliases")]
+        fn error_policy_aliases_py(&self) -> Vec<&'static str> {
+            self.aliases().to_vec()
+        }
 
-    #[staticmethod]
-    #[pyo3(name = "from_alias")]
-    fn from_alias_py(alias: String) -> PyResult<ParamType> {
-        ParamType::from_alias(&alias).map_err(|e| PyValueError::new_err(e))
+        #[staticmethod]
+        #[pyo3(name = "from_alias")]
+        fn from_alias_py(alias: String) -> PyResult<ErrorPolicy> {
+            ErrorPolicy::from_alias(&alias).map_err(PyValueError::new_err)
+        }
     }
-}
+};
 
 #[pymethods]
-impl EtlStage {
+impl ErrorKind {
     pub fn __str__(&self) -> &'static str {
         self.name()
     }
 
     pub fn __repr__(&self) -> String {
-        format!("EtlStage.{}", self.name().to_uppercase())
-    }
-
-    #[getter]
-    #[pyo3(name = "name")]
-    fn stage_name_py(&self) -> &'static str {
-        self.name()
-    }
-    #[getter]
-    #[pyo3(name = "aliases")]
-    fn aliases_py(&self) -> Vec<&'static str> {
-        self.aliases().to_vec()
+        format!("ErrorKind.{}", self.name().to_uppercase())
     }
 
     #[getter]
     #[pyo3(name = "id")]
-    fn stage_id_py(&self) -> u8 {
+    fn error_kind_id_py(&self) -> u8 {
         self.id()
     }
 
-    #[staticmethod]
-    #[pyo3(name = "from_alias")]
-    fn from_alias_py(alias: String) -> PyResult<EtlStage> {
-        EtlStage::from_alias(&alias).map_err(|e| PyValueError::new_err(e))
+    #[getter]
+    #[pyo3(name = "name")]
+    fn error_kind_name_py(&self) -> &'static str {
+        self.name()
     }
 }
 
 #[pymethods]
-impl SystemType {
+impl ChangeKind {
     pub fn __str__(&self) -> &'static str {
         self.name()
     }
 
     pub fn __repr__(&self) -> String {
-        format!("SystemType.{}", self.name().to_uppercase())
+        format!("ChangeKind.{}", self.name().to_uppercase())
     }
 
     #[getter]
     #[pyo3(name = "id")]
-    fn system_type_id_py(&self) -> u8 {
+    fn change_kind_id_py(&self) -> u8 {
         self.id()
     }
 
     #[getter]
     #[pyo3(name = "name")]
-    fn system_type_name_py(&self) -> &'static str {
+    fn change_kind_name_py(&self) -> &'static str {
         self.name()
     }
-    #[getter]
-    #[pyo3(name = "aliases")]
-    fn system_type_aliases_py(&self) -> Vec<&'static str> {
-        self.aliases().to_vec()
-    }
-
-    #[staticmethod]
-    #[pyo3(name = "from_alias")]
-    fn from_alias_py(alias: String) -> PyResult<SystemType> {
-        SystemType::from_alias(&alias).map_err(|e| PyValueError::new_err(e))
-    }
 }
 
 #[pymethods]
-impl TaskType {
+impl SegmentRole {
     pub fn __str__(&self) -> &'static str {
         self.name()
     }
 
     pub fn __repr__(&self) -> String {
-        format!("TaskType.{}", self.name().to_uppercase())
+        format!("SegmentRole.{}", self.name().to_uppercase())
     }
 
     #[getter]
     #[pyo3(name = "id")]
-    fn task_type_id_py(&self) -> u8 {
+    fn segment_role_id_py(&self) -> u8 {
         self.id()
     }
 
     #[getter]
     #[pyo3(name = "name")]
-    fn task_type_name_py(&self) -> &'static str {
+    fn segment_role_name_py(&self) -> &'static str {
         self.name()
     }
-    #[getter]
-    #[pyo3(name = "extensions")]
-    fn task_type_extensions_py(&self) -> Vec<&'static str> {
-        self.extensions().to_vec()
-    }
-
-    #[staticmethod]
-    #[pyo3(name = "from_extension")]
-    fn from_extension_py(extension: String) -> PyResult<TaskType> {
-        TaskType::from_extension(&extension).map_err(|e| PyValueError::new_err(e))
-    }
 }
 
-#[pymethods]
-impl Subtask {
-    #[new]
-    #[pyo3(signature = (name="".to_string(), path=None, stage=None, entity=None, system_type=None, task_type=None, is_common=false, command=None))]
-    pub fn py_new(
-        name: String,
-        path: Option<String>,
-        stage: Option<EtlStage>,
-        entity: Option<String>,
-        system_type: Option<SystemType>,
-        task_type: Option<TaskType>,
-        is_common: bool,
-        command: Option<String>,
-    ) -> Self {
-        let resolved_path = path.unwrap_or_else(|| name.clone());
-        Subtask {
-            original_name: name.clone(),
-            original_path: resolved_path.clone(),
-            name,
-            path: resolved_path,
-            task_type,
-            system_type,
-            stage,
-            entity,
-            is_common,
-            command,
-            rendered_command: None,
-            params: None,
-            stored_params: None,
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl EtlStage {
+        pub fn __str__(&self) -> &'static str {
+            self.name()
         }
-    }
 
-    #[getter]
-    #[pyo3(name = "original_name")]
-    pub fn original_name_py(&self) -> String {
-        self.original_name.clone()
-    }
+        pub fn __repr__(&self) -> String {
+            format!("EtlStage.{}", self.name().to_uppercase())
+        }
 
-    #[getter]
-    #[pyo3(name = "original_path")]
-    pub fn original_path_py(&self) -> String {
-        self.original_path.clone()
-    }
+        #[getter]
+        #[pyo3(name = "name")]
+        fn stage_name_py(&self) -> &'static str {
+            self.name()
+        }
+        #[getter]
+        #[pyo3(name = "aliases")]
+        fn aliases_py(&self) -> Vec<&'static str> {
+            self.aliases().to_vec()
+        }
 
-    pub fn __repr__(&self) -> String {
-        format!(
-            "Subtask(name='{}', path='{}', stage={:?}, entity={:?}, system_type={:?}, task_type={:?}, is_common={})",
-            self.name,
-            self.path,
-            self.stage,
-            self.entity,
-            self.system_type,
-            self.task_type,
-            self.is_common
-        )
-    }
+        #[getter]
+        #[pyo3(name = "id")]
+        fn stage_id_py(&self) -> u8 {
+            self.id()
+        }
 
-    pub fn __str__(&self) -> String {
-        self.__repr__()
-    }
-    #[pyo3(name = "get_stored_params")]
-    pub fn get_stored_params_py(&self, py: Python) -> PyResult<PyObject> {
-        if let Some(stored) = &self.stored_params {
-            // convert HashMap<String,String> -> Python dict
-            let dict = PyDict::new_bound(py);
-            for (k, v) in stored {
-                dict.set_item(k, v)?;
+        /// Position in the pipeline, shared with custom stages
+        #[getter]
+        #[pyo3(name = "ordinal")]
+        fn stage_ordinal_py(&self) -> i64 {
+            self.ordinal()
+        }
+
+        #[staticmethod]
+        #[pyo3(name = "from_alias")]
+        #[pyo3(signature = (alias, aliases=None))]
+        fn from_alias_py(
+            alias: String,
+            aliases: Option<PyRef<'_, AliasRegistry>>,
+        ) -> PyResult<Stage> {
+            match aliases.and_then(|registry| registry.stage(&alias)) {
+                Some(stage) => Ok(stage),
+                None => EtlStage::from_alias(&alias)
+                    .map(Stage::Builtin)
+                    .map_err(PyValueError::new_err),
             }
-            Ok(dict.into())
-        } else {
-            // return empty dict
-            Ok(PyDict::new_bound(py).into())
         }
     }
+};
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl SystemType {
+        pub fn __str__(&self) -> &'static str {
+            self.name()
+        }
 
-    #[pyo3(name = "get_params")]
-    #[pyo3(signature = (styles=None))]
-    pub fn get_params_py(
-        &self,
-        styles: Option<Vec<ParamType>>, // optional param styles from Python
-        py: Python,                     // we need the GIL to build Python objects
-    ) -> PyResult<PyObject> {
-        // Map style names (strings) → ParamType
+        pub fn __repr__(&self) -> String {
+            format!("SystemType.{}", self.name().to_uppercase())
+        }
 
-        // Call the Rust implementation
-        let params = self.get_params(styles.as_deref());
+        #[getter]
+        #[pyo3(name = "id")]
+        fn system_type_id_py(&self) -> u8 {
+            self.id()
+        }
 
-        // Convert HashSet<String> → Python set
-        let pyset = PySet::empty_bound(py)?;
-        for name in params {
-            pyset.add(name)?;
+        #[getter]
+        #[pyo3(name = "name")]
+        fn system_type_name_py(&self) -> &'static str {
+            self.name()
+        }
+        #[getter]
+        #[pyo3(name = "aliases")]
+        fn system_type_aliases_py(&self) -> Vec<&'static str> {
+            self.aliases().to_vec()
         }
 
-        Ok(pyset.into())
+        #[staticmethod]
+        #[pyo3(name = "from_alias")]
+        #[pyo3(signature = (alias, aliases=None))]
+        fn from_alias_py(
+            alias: String,
+            aliases: Option<PyRef<'_, AliasRegistry>>,
+        ) -> PyResult<System> {
+            match aliases.and_then(|registry| registry.system(&alias)) {
+                Some(system) => Ok(system),
+                None => SystemType::from_alias(&alias)
+                    .map(System::Builtin)
+                    .map_err(PyValueError::new_err),
+            }
+        }
     }
+};
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl TaskType {
+        pub fn __str__(&self) -> &'static str {
+            self.name()
+        }
 
-    /// Get the command to execute. Returns rendered_command if available, otherwise command template.
-    #[pyo3(name = "get_command")]
-    pub fn get_command_py(&self) -> Option<String> {
-        self.get_command().cloned()
-    }
+        pub fn __repr__(&self) -> String {
+            format!("TaskType.{}", self.name().to_uppercase())
+        }
 
-    /// Render this subtask - resolves all templates even if no parameters are needed.
-    /// Equivalent to calling apply_parameters with empty dict.
-    #[pyo3(name = "render")]
-    pub fn render_py(&self, py: Python) -> PyResult<Py<Subtask>> {
-        let rendered = self.render();
-        Py::new(py, rendered)
-    }
+        #[getter]
+        #[pyo3(name = "id")]
+        fn task_type_id_py(&self) -> u8 {
+            self.id()
+        }
+
+        #[getter]
+        #[pyo3(name = "name")]
+        fn task_type_name_py(&self) -> &'static str {
+            self.name()
+        }
+        #[getter]
+        #[pyo3(name = "extensions")]
+        fn task_type_extensions_py(&self) -> Vec<&'static str> {
+            self.extensions().to_vec()
+        }
 
-    /// Lightweight render - returns only the rendered values without metadata.
-    /// More efficient than render() or apply_parameters() for simple use cases.
-    #[pyo3(name = "render_lightweight")]
-    pub fn render_lightweight_py(&self, py: Python) -> PyResult<Py<RenderedSubtask>> {
-        let rendered = self.render_lightweight();
-        Py::new(py, rendered)
+        #[staticmethod]
+        #[pyo3(name = "from_extension")]
+        fn from_extension_py(extension: String) -> PyResult<TaskType> {
+            TaskType::from_extension(&extension).map_err(PyValueError::new_err)
+        }
     }
+};
+
+#[allow(clippy::useless_conversion)]
+const _: () = {
+    #[pymethods]
+    impl Subtask {
+        #[new]
+        #[pyo3(signature = (name="".to_string(), path=None, stage=None, entity=None, system_type=None, task_type=None, is_common=false, command=None))]
+        #[allow(clippy::too_many_arguments)]
+        pub fn py_new(
+            name: String,
+            path: Option<String>,
+            stage: Option<StageArg>,
+            entity: Option<String>,
+            system_type: Option<SystemArg>,
+            task_type: Option<TaskType>,
+            is_common: bool,
+            command: Option<String>,
+        ) -> PyResult<Self> {
+            let resolved_path = path.unwrap_or_else(|| name.clone());
+            let entity_path = entity
+                .iter()
+                .flat_map(|e| e.split('/'))
+                .filter(|p| !p.is_empty())
+                .map(|p| p.to_string())
+                .collect();
+            let registry = AliasRegistry::default();
+            let stage = stage
+                .map(|stage| registry.resolve_stage_arg(stage))
+                .transpose()?;
+            let system = system_type
+                .map(|system| registry.resolve_system_arg(system))
+                .transpose()?;
+            let mut subtask = Subtask {
+                original_name: name.clone(),
+                original_path: resolved_path.clone(),
+                name,
+                path: resolved_path,
+                task_type,
+                system_type: None,
+                custom_system: None,
+                stage: None,
+                custom_stage: None,
+                entity,
+                entity_path,
+                is_common,
+                metadata: BTreeMap::new(),
+                command,
+                lazy_command: None,
+                encoding: None,
+                size_bytes: None,
+                modified_at: None,
+                rendered_command: None,
+                params: None,
+                stored_params: None,
+            };
+            subtask.set_stage(stage);
+            subtask.set_system(system);
+            Ok(subtask)
+        }
 
-    /// Apply parameters and return a lightweight RenderedSubtask with only the output values.
-    /// More efficient than apply_parameters() which returns a full Subtask clone.
-    #[pyo3(signature = (params, styles=None, ignore_missing=None))]
-    #[pyo3(name = "render_with_params")]
-    pub fn render_with_params_py(
-        &self,
-        py: Python,
-        params: &Bound<'_, PyDict>,
-        styles: Option<Vec<ParamType>>,
-        ignore_missing: Option<bool>,
-    ) -> PyResult<Py<RenderedSubtask>> {
-        // convert params to HashMap<String,String>
-        let mut map = HashMap::new();
-        for item in params.items() {
-            let (k, v): (Bound<PyAny>, Bound<PyAny>) = item.extract()?;
-            let key = k.extract::<String>()?;
-
-            // Convert any Python object to string using its __str__ method
-            let val = v.str()?.to_string();
-            map.insert(key, val);
-        }
-
-        let ignore_missing = ignore_missing.unwrap_or(false);
-
-        // call the Rust render_with_params
-        match self.render_with_params(&map, styles.as_deref(), ignore_missing) {
-            Ok(rendered) => Py::new(py, rendered),
-            Err(e) => Err(PyValueError::new_err(e)),
-        }
-    }
-
-    /// Apply parameters from a Python dict to the subtask.
-    /// Returns a new Subtask instance with parameters applied (immutable operation).
-    /// params: dict-like mapping string->string
-    /// styles: optional list of ParamType names, e.g. ["DollarBrace", "Curly"]
-    /// ignore_missing: if true, missing placeholders are left unchanged; if false, raises ValueError
-    #[pyo3(signature = (params, styles=None, ignore_missing=None))]
-    #[pyo3(name = "apply_parameters")]
-    pub fn apply_parameters_py(
-        &self,
-        py: Python,
-        params: &Bound<'_, PyDict>,
-        styles: Option<Vec<ParamType>>,
-        ignore_missing: Option<bool>,
-    ) -> PyResult<Py<Subtask>> {
-        // convert params to HashMap<String,String>
-        let mut map = HashMap::new();
-        for item in params.items() {
-            let (k, v): (Bound<PyAny>, Bound<PyAny>) = item.extract()?;
-            let key = k.extract::<String>()?;
-
-            // Convert any Python object to string using its __str__ method
-            let val = v.str()?.to_string();
-            map.insert(key, val);
-        }
-
-        let ignore_missing = ignore_missing.unwrap_or(false);
-
-        // call the Rust apply_parameters (returns new Subtask)
-        match self.apply_parameters(&map, styles.as_deref(), ignore_missing) {
-            Ok(new_subtask) => {
-                // Return the new Subtask as a Python object
-                Py::new(py, new_subtask)
+        /// Stage: an `EtlStage` member or a `CustomStage`
+        #[getter]
+        #[pyo3(name = "stage")]
+        pub fn stage_py(&self) -> Option<Stage> {
+            self.pipeline_stage()
+        }
+
+        /// Target system: a `SystemType` member or a `CustomSystem`
+        #[getter]
+        #[pyo3(name = "system_type")]
+        pub fn system_type_py(&self) -> Option<System> {
+            self.system()
+        }
+
+        #[getter]
+        #[pyo3(name = "original_name")]
+        pub fn original_name_py(&self) -> String {
+            self.original_name.clone()
+        }
+
+        #[getter]
+        #[pyo3(name = "original_path")]
+        pub fn original_path_py(&self) -> String {
+            self.original_path.clone()
+        }
+
+        /// Template command; file-backed subtasks read their file on first access
+        #[getter]
+        #[pyo3(name = "command")]
+        pub fn command_py(&self) -> PyResult<Option<String>> {
+            loaded_command(self)
+        }
+
+        /// Encoding the task file was decoded from (e.g. `utf-8`, `windows-1251`);
+        /// reads the file on first access like `command`
+        #[getter]
+        #[pyo3(name = "encoding")]
+        pub fn encoding_py(&self) -> PyResult<Option<String>> {
+            self.load_encoding().map_err(|e| {
+                let error = SubtaskError::from_anyhow(&self.path, &e);
+                pyo3::exceptions::PyRuntimeError::new_err(error.__str__())
+            })
+        }
+
+        /// SHA-256 (hex) of the template command; reads the file on first access like `command`
+        #[getter]
+        #[pyo3(name = "content_hash")]
+        pub fn content_hash_py(&self) -> PyResult<Option<String>> {
+            self.content_hash().map_err(|e| {
+                let error = SubtaskError::from_anyhow(&self.path, &e);
+                pyo3::exceptions::PyRuntimeError::new_err(error.__str__())
+            })
+        }
+
+        /// Whether the template command is currently in memory
+        #[getter]
+        #[pyo3(name = "is_loaded")]
+        pub fn is_loaded_py(&self) -> bool {
+            self.is_loaded()
+        }
+
+        /// Drop lazily read content to free memory; it is read again on next access
+        #[pyo3(name = "release")]
+        pub fn release_py(&self) {
+            self.release()
+        }
+
+        pub fn __repr__(&self) -> String {
+            format!(
+                "Subtask(name='{}', path='{}', stage={:?}, entity={:?}, system_type={:?}, task_type={:?}, is_common={})",
+                self.name,
+                self.path,
+                self.pipeline_stage().map(|stage| stage.name().to_string()),
+                self.entity,
+                self.system().map(|system| system.name().to_string()),
+                self.task_type,
+                self.is_common
+            )
+        }
+
+        pub fn __str__(&self) -> String {
+            self.__repr__()
+        }
+        #[pyo3(name = "get_stored_params")]
+        pub fn get_stored_params_py(&self, py: Python) -> PyResult<PyObject> {
+            if let Some(stored) = &self.stored_params {
+                // convert HashMap<String,String> -> Python dict
+                let dict = PyDict::new_bound(py);
+                for (k, v) in stored {
+                    dict.set_item(k, v)?;
+                }
+                Ok(dict.into())
+            } else {
+                // return empty dict
+                Ok(PyDict::new_bound(py).into())
+            }
+        }
+
+        #[pyo3(name = "get_params")]
+        #[pyo3(signature = (styles=None))]
+        pub fn get_params_py(
+            &self,
+            styles: Option<Vec<ParamType>>, // optional param styles from Python
+            py: Python,                     // we need the GIL to build Python objects
+        ) -> PyResult<PyObject> {
+            // Make read errors visible instead of silently ignoring the command
+            loaded_command(self)?;
+
+            // Call the Rust implementation
+            let params = self
+                .get_params(styles.as_deref())
+                .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+
+            // Convert HashSet<String> → Python set
+            let pyset = PySet::empty_bound(py)?;
+            for name in params {
+                pyset.add(name)?;
+            }
+
+            Ok(pyset.into())
+        }
+
+        /// Get the command to execute. Returns rendered_command if available, otherwise command template.
+        #[pyo3(name = "get_command")]
+        pub fn get_command_py(&self) -> PyResult<Option<String>> {
+            match &self.rendered_command {
+                Some(rendered) => Ok(Some(rendered.clone())),
+                None => loaded_command(self),
+            }
+        }
+
+        /// Render this subtask - resolves all templates even if no parameters are needed.
+        /// Equivalent to calling apply_parameters with empty dict.
+        #[pyo3(name = "render")]
+        pub fn render_py(&self, py: Python) -> PyResult<Py<Subtask>> {
+            loaded_command(self)?;
+            let rendered = self
+                .render()
+                .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+            Py::new(py, rendered)
+        }
+
+        /// Lightweight render - returns only the rendered values without metadata.
+        /// More efficient than render() or apply_parameters() for simple use cases.
+        #[pyo3(name = "render_lightweight")]
+        pub fn render_lightweight_py(&self, py: Python) -> PyResult<Py<RenderedSubtask>> {
+            loaded_command(self)?;
+            let rendered = self
+                .render_lightweight()
+                .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+            Py::new(py, rendered)
+        }
+
+        /// Apply parameters and return a lightweight RenderedSubtask with only the output values.
+        /// More efficient than apply_parameters() which returns a full Subtask clone.
+        #[pyo3(signature = (params, styles=None, ignore_missing=None))]
+        #[pyo3(name = "render_with_params")]
+        pub fn render_with_params_py(
+            &self,
+            py: Python,
+            params: &Bound<'_, PyDict>,
+            styles: Option<Vec<ParamType>>,
+            ignore_missing: Option<bool>,
+        ) -> PyResult<Py<RenderedSubtask>> {
+            // convert params to HashMap<String,String>
+            let mut map = HashMap::new();
+            for item in params.items() {
+                let (k, v): (Bound<PyAny>, Bound<PyAny>) = item.extract()?;
+                let key = k.extract::<String>()?;
+
+                // Convert any Python object to string using its __str__ method
+                let val = v.str()?.to_string();
+                map.insert(key, val);
+            }
+
+            let ignore_missing = ignore_missing.unwrap_or(false);
+            loaded_command(self)?;
+
+            // call the Rust render_with_params
+            match self.render_with_params(&map, styles.as_deref(), ignore_missing) {
+                Ok(rendered) => Py::new(py, rendered),
+                Err(e) => Err(PyValueError::new_err(e)),
+            }
+        }
+
+        /// Apply parameters from a Python dict to the subtask.
+        /// Returns a new Subtask instance with parameters applied (immutable operation).
+        /// params: dict-like mapping string->string
+        /// styles: optional list of ParamType names, e.g. ["DollarBrace", "Curly"]
+        /// ignore_missing: if true, missing placeholders are left unchanged; if false, raises ValueError
+        #[pyo3(signature = (params, styles=None, ignore_missing=None))]
+        #[pyo3(name = "apply_parameters")]
+        pub fn apply_parameters_py(
+            &self,
+            py: Python,
+            params: &Bound<'_, PyDict>,
+            styles: Option<Vec<ParamType>>,
+            ignore_missing: Option<bool>,
+        ) -> PyResult<Py<Subtask>> {
+            // convert params to HashMap<String,String>
+            let mut map = HashMap::new();
+            for item in params.items() {
+                let (k, v): (Bound<PyAny>, Bound<PyAny>) = item.extract()?;
+                let key = k.extract::<String>()?;
+
+                // Convert any Python object to string using its __str__ method
+                let val = v.str()?.to_string();
+                map.insert(key, val);
+            }
+
+            let ignore_missing = ignore_missing.unwrap_or(false);
+            loaded_command(self)?;
+
+            // call the Rust apply_parameters (returns new Subtask)
+            match self.apply_parameters(&map, styles.as_deref(), ignore_missing) {
+                Ok(new_subtask) => {
+                    // Return the new Subtask as a Python object
+                    Py::new(py, new_subtask)
+                }
+                Err(e) => Err(PyValueError::new_err(e)),
             }
-            Err(e) => Err(PyValueError::new_err(e)),
         }
     }
+};
+
+/// Read the template command of `subtask`, raising RuntimeError if its file cannot be read
+fn loaded_command(subtask: &Subtask) -> PyResult<Option<String>> {
+    subtask.load_command().map_err(|e| {
+        let error = SubtaskError::from_anyhow(&subtask.path, &e);
+        pyo3::exceptions::PyRuntimeError::new_err(error.__str__())
+    })
 }
 
 #[pymethods]
 impl RenderedSubtask {
+    /// SHA-256 (hex) of the rendered command
+    #[getter]
+    #[pyo3(name = "content_hash")]
+    pub fn content_hash_py(&self) -> Option<String> {
+        self.content_hash()
+    }
+
     pub fn __repr__(&self) -> String {
         format!(
             "RenderedSubtask(name='{}', path='{}', command={:?}, params={:?})",
@@ -573,7 +1709,124 @@ fn _core(m: Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<SystemType>()?;
     m.add_class::<ParamType>()?;
     m.add_class::<TaskType>()?;
+    m.add_class::<SortOrder>()?;
+    m.add_class::<ErrorPolicy>()?;
+    m.add_class::<ErrorKind>()?;
+    m.add_class::<ChangeKind>()?;
+    m.add_class::<SegmentRole>()?;
+    m.add_class::<SubtaskError>()?;
     m.add_class::<FileScanner>()?;
+    m.add_class::<ScanError>()?;
+    m.add_class::<ChangeSet>()?;
+    m.add_class::<SubtaskEvent>()?;
     m.add_class::<FileClassifier>()?;
+    m.add_class::<Explanation>()?;
+    m.add_class::<SegmentMatch>()?;
+    m.add_class::<AliasRegistry>()?;
+    m.add_class::<CustomStage>()?;
+    m.add_class::<CustomSystem>()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_source::LocalSource;
+
+    #[test]
+    fn test_from_memory() {
+        let files = HashMap::from([
+            ("customers/01_extract/pg/a.sql", "SELECT 1;"),
+            ("customers/02_transform/b.sql", "SELECT 2;"),
+            ("customers/notes.txt", "not a task"),
+            ("../outside.sql", "SELECT 3;"),
+        ])
+        .into_iter()
+        .map(|(path, text)| (path.to_string(), text.to_string()))
+        .collect();
+        let scanner = FileScanner::with_extensions(vec![".sql".to_string()]);
+        let manager =
+            SubtaskManager::from_memory(files, scanner, ErrorPolicy::Raise, true).unwrap();
+
+        assert_eq!(manager.base_path, "");
+        assert_eq!(
+            manager.file_paths,
+            [
+                "customers/01_extract/pg/a.sql",
+                "customers/02_transform/b.sql"
+            ]
+        );
+        assert_eq!(manager.scanner.scan_errors().len(), 1);
+
+        let built = build_subtask(
+            &manager.classifier,
+            &manager.source,
+            &manager.decoder,
+            None,
+            manager.lazy,
+            &manager.file_paths[0],
+            None,
+        );
+        let subtask = built.loaded.unwrap();
+        assert_eq!(subtask.stage, Some(EtlStage::Extract));
+        assert_eq!(
+            subtask.load_command().unwrap().as_deref(),
+            Some("SELECT 1;")
+        );
+    }
+    #[test]
+    fn test_events() {
+        let dir = std::env::temp_dir().join(format!("subtask-events-{}", std::process::id()));
+        let pg = dir.join("customers/01_extract/pg");
+        std::fs::create_dir_all(&pg).unwrap();
+        std::fs::write(pg.join("a.sql"), "SELECT 1;").unwrap();
+
+        let base = dir.to_string_lossy().into_owned();
+        let decoder = TextDecoder::new(&[], false, Some(64), true).unwrap();
+        let manager = SubtaskManager::open_source(
+            base.clone(),
+            Arc::new(LocalSource::new(&base)),
+            None,
+            FileScanner::with_extensions(vec!["sql".to_string()]),
+            FileClassifier::default(),
+            ErrorPolicy::Raise,
+            true,
+            Arc::new(decoder),
+        )
+        .unwrap();
+        let mut events = manager.events(Duration::from_millis(50)).unwrap();
+
+        std::fs::write(pg.join("a.sql"), "SELECT 1, 2;").unwrap();
+        std::fs::write(pg.join("b.sql"), "SELECT 3;").unwrap();
+        std::fs::write(pg.join("big.sql"), "-- padding\n".repeat(10)).unwrap();
+        let mut seen = Vec::new();
+        while seen.len() < 3 {
+            seen.extend(events.next().unwrap());
+        }
+        seen.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let kinds: Vec<_> = seen
+            .iter()
+            .map(|e| (e.path.rsplit('/').next().unwrap(), e.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("a.sql", ChangeKind::Modified),
+                ("b.sql", ChangeKind::Added),
+                ("big.sql", ChangeKind::Added),
+            ]
+        );
+        let modified = seen[0].subtask.as_ref().unwrap();
+        assert_eq!(modified.stage, Some(EtlStage::Extract));
+        assert_eq!(
+            modified.load_command().unwrap().as_deref(),
+            Some("SELECT 1, 2;")
+        );
+        assert!(seen[1].subtask.is_some());
+        // the size limit applies to watched files as it does to loaded ones
+        assert!(seen[2].subtask.is_none());
+        assert_eq!(seen[2].error.as_ref().unwrap().kind, ErrorKind::TooLarge);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
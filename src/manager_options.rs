@@ -0,0 +1,81 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::sync::Arc;
+use strum::IntoEnumIterator;
+
+use crate::decoding::TextDecoder;
+use crate::enums::{SortOrder, TaskType};
+use crate::file_classifier::FileClassifier;
+use crate::file_scanner::FileScanner;
+
+/// File extensions of every TaskType, the set a manager scans for
+fn task_extensions() -> Vec<String> {
+    TaskType::iter()
+        .flat_map(|task_type| {
+            task_type
+                .extensions()
+                .iter()
+                .map(|&s| s.to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Scanning, decoding and classification options shared by every `SubtaskManager`
+/// constructor. Each constructor fills in every field, so an option added here has
+/// to be accepted by all of them.
+pub struct ManagerOptions<'a, 'py> {
+    pub respect_ignore_files: bool,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub sort_by: SortOrder,
+    pub strict: bool,
+    pub follow_symlinks: bool,
+    pub include_hidden: bool,
+    pub max_depth: Option<usize>,
+    pub threads: Option<usize>,
+    pub fallback_encodings: Option<Vec<String>>,
+    pub normalize_newlines: bool,
+    pub max_file_size: Option<u64>,
+    pub detect_binary: bool,
+    pub layout: Option<&'a str>,
+    pub max_entity_depth: Option<usize>,
+    pub aliases: Option<&'a Bound<'py, PyAny>>,
+    pub strict_classification: bool,
+    pub read_headers: bool,
+}
+
+impl ManagerOptions<'_, '_> {
+    /// Scanner, classifier and decoder configured by these options
+    pub fn build_scanner_and_classifier(
+        self,
+    ) -> PyResult<(FileScanner, FileClassifier, Arc<TextDecoder>)> {
+        let scanner = FileScanner::new(
+            task_extensions(),
+            self.respect_ignore_files,
+            self.include,
+            self.exclude,
+            self.sort_by,
+            self.strict,
+            self.follow_symlinks,
+            self.include_hidden,
+            self.max_depth,
+            self.threads,
+        )?;
+        let decoder = TextDecoder::new(
+            &self.fallback_encodings.unwrap_or_default(),
+            self.normalize_newlines,
+            self.max_file_size,
+            self.detect_binary,
+        )
+        .map(Arc::new)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let classifier = FileClassifier::default()
+            .with_layout(self.layout)?
+            .with_max_entity_depth(self.max_entity_depth)?
+            .with_aliases(self.aliases)?
+            .with_strict(self.strict_classification)
+            .with_read_headers(self.read_headers);
+        Ok((scanner, classifier, decoder))
+    }
+}
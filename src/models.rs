@@ -1,9 +1,14 @@
 use crate::enums::{EtlStage, ParamType, SystemType, TaskType};
+use crate::file_loader::LazyCommand;
+use crate::stages::{CustomStage, Stage};
+use crate::systems::{CustomSystem, System};
 use once_cell::sync::OnceCell;
 use pyo3::prelude::*;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 
 #[pyclass]
@@ -22,17 +27,38 @@ pub struct Subtask {
     pub path: String,
     #[pyo3(get)]
     pub task_type: Option<TaskType>,
-    #[pyo3(get)]
+    /// Built-in system; None for tasks of a custom system, see `system()`
     pub system_type: Option<SystemType>,
-    #[pyo3(get)]
+    /// Registered custom system the task targets, if it is not a built-in one
+    pub custom_system: Option<CustomSystem>,
+    /// Built-in stage; None for tasks of a custom stage, see `pipeline_stage()`
     pub stage: Option<EtlStage>,
+    /// Registered custom stage of the task, if it is not a built-in one
+    pub custom_stage: Option<CustomStage>,
+    /// Entity folders joined with `/`
     #[pyo3(get)]
     pub entity: Option<String>,
+    /// Entity folders from the outermost in; empty for tasks without an entity
+    #[pyo3(get)]
+    pub entity_path: Vec<String>,
     #[pyo3(get)]
     pub is_common: bool,
-    /// Template command (never mutated)
+    /// Header entries of the task file other than stage, system and entity
     #[pyo3(get)]
+    pub metadata: BTreeMap<String, String>,
+    /// Template command (never mutated); see `lazy_command` for file-backed subtasks
     pub command: Option<String>,
+    /// Source of the template command when it has not been read yet
+    #[serde(skip)]
+    pub lazy_command: Option<Arc<LazyCommand>>,
+    /// Encoding `command` was decoded from; see `load_encoding` for lazy subtasks
+    pub encoding: Option<String>,
+    /// Size of the source file when it was scanned
+    #[pyo3(get)]
+    pub size_bytes: Option<u64>,
+    /// Modification time of the source file when it was scanned, in seconds since the epoch
+    #[pyo3(get)]
+    pub modified_at: Option<f64>,
 
     /// Rendered command with parameters applied
     #[pyo3(get)]
@@ -57,6 +83,21 @@ pub struct RenderedSubtask {
     pub params: HashMap<String, String>,
 }
 
+/// Lowercase hex SHA-256 of `text`, a stable fingerprint of a task's SQL
+pub fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl RenderedSubtask {
+    /// SHA-256 of the rendered command
+    pub fn content_hash(&self) -> Option<String> {
+        self.command.as_deref().map(sha256_hex)
+    }
+}
+
 impl Subtask {
     pub fn new(path: &str) -> Self {
         let p = std::path::Path::new(path);
@@ -71,15 +112,104 @@ impl Subtask {
             path: path.to_string(),
             task_type: None,
             system_type: None,
+            custom_system: None,
             stage: None,
+            custom_stage: None,
             entity: None,
+            entity_path: Vec::new(),
             is_common: false,
+            metadata: BTreeMap::new(),
             command: None,
+            lazy_command: None,
+            encoding: None,
+            size_bytes: None,
+            modified_at: None,
             params: None,
             stored_params: None,
             rendered_command: None,
         }
     }
+
+    /// Stage of the task, built-in or custom
+    pub fn pipeline_stage(&self) -> Option<Stage> {
+        match &self.custom_stage {
+            Some(custom) => Some(Stage::Custom(custom.clone())),
+            None => self.stage.map(Stage::Builtin),
+        }
+    }
+
+    pub fn set_stage(&mut self, stage: Option<Stage>) {
+        (self.stage, self.custom_stage) = match stage {
+            Some(Stage::Builtin(stage)) => (Some(stage), None),
+            Some(Stage::Custom(custom)) => (None, Some(custom)),
+            None => (None, None),
+        };
+    }
+
+    /// System the task targets, built-in or custom
+    pub fn system(&self) -> Option<System> {
+        match &self.custom_system {
+            Some(custom) => Some(System::Custom(custom.clone())),
+            None => self.system_type.map(System::Builtin),
+        }
+    }
+
+    pub fn set_system(&mut self, system: Option<System>) {
+        (self.system_type, self.custom_system) = match system {
+            Some(System::Builtin(system_type)) => (Some(system_type), None),
+            Some(System::Custom(custom)) => (None, Some(custom)),
+            None => (None, None),
+        };
+    }
+
+    /// Set `entity_path` and the joined `entity` from the entity folders
+    pub fn set_entity_path(&mut self, entity_path: Vec<String>) {
+        self.entity = (!entity_path.is_empty()).then(|| entity_path.join("/"));
+        self.entity_path = entity_path;
+    }
+
+    /// Whether the entity path starts with the folders of `prefix` (`a/b`)
+    pub fn entity_starts_with(&self, prefix: &str) -> bool {
+        let prefix: Vec<&str> = prefix.split('/').filter(|p| !p.is_empty()).collect();
+        self.entity_path.len() >= prefix.len()
+            && self.entity_path.iter().zip(&prefix).all(|(a, b)| a == b)
+    }
+
+    /// SHA-256 of the template command, reading it from the source file on first use
+    pub fn content_hash(&self) -> anyhow::Result<Option<String>> {
+        Ok(self.load_command()?.as_deref().map(sha256_hex))
+    }
+
+    /// Template command, reading it from the source file on first use.
+    /// Returns Err only when the file has to be read and cannot be.
+    pub fn load_command(&self) -> anyhow::Result<Option<String>> {
+        match (&self.command, &self.lazy_command) {
+            (Some(cmd), _) => Ok(Some(cmd.clone())),
+            (None, Some(lazy)) => lazy.get().map(|decoded| Some(decoded.text)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Encoding the task file was decoded from, reading it on first use like `load_command`
+    pub fn load_encoding(&self) -> anyhow::Result<Option<String>> {
+        match (&self.command, &self.lazy_command) {
+            (Some(_), _) => Ok(self.encoding.clone()),
+            (None, Some(lazy)) => lazy.get().map(|decoded| Some(decoded.encoding)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Whether the template command is in memory
+    pub fn is_loaded(&self) -> bool {
+        self.command.is_some() || self.lazy_command.as_ref().is_some_and(|l| l.is_loaded())
+    }
+
+    /// Drop lazily read content; it is read again on next access
+    pub fn release(&self) {
+        if let Some(lazy) = &self.lazy_command {
+            lazy.release();
+        }
+    }
     fn default_param_styles() -> Vec<ParamType> {
         ParamType::iter()
             .filter(|style| *style != ParamType::Other)
@@ -145,19 +275,21 @@ impl Subtask {
         new_subtask
     }
 
-    /// Extract parameters from path and command, return new Subtask with params set
-    pub fn extract_params(&self, styles: Option<&[ParamType]>) -> Self {
-        let all_params = self.get_params(styles);
+    /// Extract parameters from path and command, return new Subtask with params set.
+    /// Returns Err when a lazily loaded command cannot be read.
+    pub fn extract_params(&self, styles: Option<&[ParamType]>) -> Result<Self, String> {
+        let all_params = self.get_params(styles)?;
 
         let mut new_subtask = self.clone();
         if !all_params.is_empty() {
             new_subtask.params = Some(all_params);
         }
-        new_subtask
+        Ok(new_subtask)
     }
 
-    /// Getter method to extract parameters (computed property)
-    pub fn get_params(&self, styles: Option<&[ParamType]>) -> HashSet<String> {
+    /// Getter method to extract parameters (computed property).
+    /// Returns Err when a lazily loaded command cannot be read.
+    pub fn get_params(&self, styles: Option<&[ParamType]>) -> Result<HashSet<String>, String> {
         let mut all_params = HashSet::new();
 
         // Extract from path
@@ -165,8 +297,8 @@ impl Subtask {
         all_params.extend(path_params);
 
         // Extract from command if present
-        if let Some(cmd) = &self.command {
-            let cmd_params = Self::detect_parameters_in_text(cmd, styles);
+        if let Some(cmd) = self.load_command().map_err(|e| e.to_string())? {
+            let cmd_params = Self::detect_parameters_in_text(&cmd, styles);
             all_params.extend(cmd_params);
         }
 
@@ -174,7 +306,7 @@ impl Subtask {
         let name_params = Self::detect_parameters_in_text(&self.name, styles);
         all_params.extend(name_params);
 
-        all_params
+        Ok(all_params)
     }
 
     /// Find parameter names according to given param styles.
@@ -243,17 +375,18 @@ impl Subtask {
 
     /// Get the command to execute. Returns rendered_command if available, otherwise command template.
     /// This is a convenience method to avoid checking both fields.
+    /// Does not read lazily loaded content; use `load_command` for that.
     pub fn get_command(&self) -> Option<&String> {
         self.rendered_command.as_ref().or(self.command.as_ref())
     }
 
     /// Render this subtask - resolves all templates even if no parameters are needed.
     /// Equivalent to calling apply_parameters with empty params.
-    pub fn render(&self) -> Self {
+    /// Returns Err only when a lazily loaded command cannot be read.
+    pub fn render(&self) -> Result<Self, String> {
         let empty_params = HashMap::new();
         // Use ignore_missing=true since we're just rendering with no params
         self.apply_parameters(&empty_params, None, true)
-            .expect("render should never fail with empty params and ignore_missing=true")
     }
 
     /// Apply parameters and return a lightweight RenderedSubtask with only the output values.
@@ -277,7 +410,8 @@ impl Subtask {
         all_missing.extend(missing_name);
 
         // command: APPLY FROM TEMPLATE
-        let rendered_command = if let Some(template_cmd) = &self.command {
+        let template = self.load_command().map_err(|e| e.to_string())?;
+        let rendered_command = if let Some(template_cmd) = &template {
             let (rendered, missing_cmd) =
                 Self::apply_parameters_to_text(template_cmd, params, styles, ignore_missing);
             all_missing.extend(missing_cmd);
@@ -304,11 +438,10 @@ impl Subtask {
     }
 
     /// Lightweight render without parameters. Returns only the rendered values.
-    pub fn render_lightweight(&self) -> RenderedSubtask {
+    /// Returns Err only when a lazily loaded command cannot be read.
+    pub fn render_lightweight(&self) -> Result<RenderedSubtask, String> {
         let empty_params = HashMap::new();
-        self.render_with_params(&empty_params, None, true).expect(
-            "render_lightweight should never fail with empty params and ignore_missing=true",
-        )
+        self.render_with_params(&empty_params, None, true)
     }
 
     /// Apply parameters to this subtask (path, command, and name). Returns new Subtask with applied parameters.
@@ -333,7 +466,8 @@ impl Subtask {
         all_missing.extend(missing_name);
 
         // command: APPLY FROM TEMPLATE → STORE IN rendered_command
-        let rendered_command = if let Some(template_cmd) = &self.command {
+        let template = self.load_command().map_err(|e| e.to_string())?;
+        let rendered_command = if let Some(template_cmd) = &template {
             let (rendered, missing_cmd) =
                 Self::apply_parameters_to_text(template_cmd, params, styles, ignore_missing);
             all_missing.extend(missing_cmd);
@@ -358,10 +492,18 @@ impl Subtask {
             path: new_path,
             task_type: self.task_type,
             system_type: self.system_type,
+            custom_system: self.custom_system.clone(),
             stage: self.stage,
+            custom_stage: self.custom_stage.clone(),
             entity: self.entity.clone(),
+            entity_path: self.entity_path.clone(),
             is_common: self.is_common,
+            metadata: self.metadata.clone(),
             command: self.command.clone(),
+            lazy_command: self.lazy_command.clone(),
+            encoding: self.encoding.clone(),
+            size_bytes: self.size_bytes,
+            modified_at: self.modified_at,
             rendered_command,
             params: self.params.clone(),
             stored_params: Some(params.clone()),
@@ -372,6 +514,7 @@ impl Subtask {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::task_source::LocalSource;
     use std::collections::HashMap;
 
     fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
@@ -387,7 +530,7 @@ mod tests {
         subtask.command = Some("psql -h $host -U ${user}".into());
 
         // Extract and store params (returns new instance)
-        let subtask = subtask.extract_params(None);
+        let subtask = subtask.extract_params(None).unwrap();
 
         // Check that params were stored
         assert!(subtask.params.is_some());
@@ -399,7 +542,7 @@ mod tests {
         assert_eq!(params.len(), 4);
 
         // Also test getter method
-        let computed_params = subtask.get_params(None);
+        let computed_params = subtask.get_params(None).unwrap();
         assert_eq!(computed_params.len(), 4);
     }
 
@@ -412,17 +555,25 @@ mod tests {
             path: "path/{date}/report_{env}.sql".to_string(),
             task_type: None,
             system_type: None,
+            custom_system: None,
             stage: None,
+            custom_stage: None,
             entity: None,
+            entity_path: Vec::new(),
             is_common: false,
+            metadata: BTreeMap::new(),
             command: Some("run $user".to_string()),
+            lazy_command: None,
+            encoding: None,
+            size_bytes: None,
+            modified_at: None,
             params: None, // Not pre-extracted
             stored_params: None,
             rendered_command: None,
         };
 
         // Use getter to compute params
-        let params = subtask.get_params(None);
+        let params = subtask.get_params(None).unwrap();
         assert!(params.contains("env"));
         assert!(params.contains("date"));
         assert!(params.contains("user"));
@@ -727,7 +878,7 @@ mod tests {
         s.command = Some("psql -h localhost -U admin".into());
 
         // Render without any parameters
-        let rendered = s.render();
+        let rendered = s.render().unwrap();
 
         // Should have rendered_command populated even though no params were replaced
         assert_eq!(
@@ -748,7 +899,7 @@ mod tests {
         s.command = Some("psql -h $host".into());
 
         // Render without providing parameters - should leave placeholders unchanged
-        let rendered = s.render();
+        let rendered = s.render().unwrap();
 
         // Placeholders remain since we used empty params with ignore_missing=true
         assert_eq!(rendered.path, "report_{env}.sql");
@@ -788,11 +939,50 @@ mod tests {
         let mut s = Subtask::new("report.sql");
         s.command = Some("psql -h localhost".into());
 
-        let rendered = s.render_lightweight();
+        let rendered = s.render_lightweight().unwrap();
 
         assert_eq!(rendered.name, "report.sql");
         assert_eq!(rendered.path, "report.sql");
         assert_eq!(rendered.command, Some("psql -h localhost".to_string()));
         assert!(rendered.params.is_empty());
     }
+
+    #[test]
+    fn test_content_hash() {
+        let mut subtask = Subtask::new("report.sql");
+        assert_eq!(subtask.content_hash().unwrap(), None);
+        subtask.command = Some("abc".to_string());
+        assert_eq!(
+            subtask.content_hash().unwrap().as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        let rendered = subtask.render_lightweight().unwrap();
+        assert_eq!(rendered.content_hash(), subtask.content_hash().unwrap());
+    }
+
+    #[test]
+    fn test_unreadable_command_is_an_error() {
+        let path = "no/such/dir/report_{env}.sql";
+        let mut subtask = Subtask::new(path);
+        let source = Arc::new(LocalSource::new("no/such/dir"));
+        subtask.lazy_command = Some(Arc::new(LazyCommand::new(path, source, Arc::default())));
+
+        assert!(subtask.render().is_err());
+        assert!(subtask.render_lightweight().is_err());
+        assert!(subtask.get_params(None).is_err());
+    }
+
+    #[test]
+    fn test_entity_path() {
+        let mut subtask = Subtask::new("finance/billing/invoices/01_extract/a.sql");
+        subtask.set_entity_path(vec!["finance".into(), "billing".into(), "invoices".into()]);
+        assert_eq!(subtask.entity.as_deref(), Some("finance/billing/invoices"));
+        assert!(subtask.entity_starts_with("finance"));
+        assert!(subtask.entity_starts_with("finance/billing/"));
+        assert!(!subtask.entity_starts_with("fin"));
+        assert!(!subtask.entity_starts_with("finance/billing/invoices/x"));
+
+        subtask.set_entity_path(Vec::new());
+        assert_eq!(subtask.entity, None);
+    }
 }
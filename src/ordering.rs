@@ -0,0 +1,122 @@
+use crate::enums::SortOrder;
+use crate::file_classifier::FileClassifier;
+use std::cmp::Ordering;
+use std::path::Path;
+
+/// Sort key used by `SortOrder::Stage`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct StageKey {
+    // files without a detected stage go after every staged file
+    stage: (bool, i64),
+    entity: Option<String>,
+    // unnumbered files go after numbered ones within the same stage and entity
+    prefix: (bool, u64),
+}
+
+impl StageKey {
+    fn for_path(classifier: &FileClassifier, path: &str) -> Self {
+        let (stage, entity) = match classifier.classify_internal(path) {
+            Ok(sub) => (sub.pipeline_stage().map(|s| s.ordinal()), sub.entity),
+            Err(_) => (None, None),
+        };
+        let prefix = Path::new(path)
+            .file_name()
+            .and_then(|n| numeric_prefix(&n.to_string_lossy()));
+        StageKey {
+            stage: (stage.is_none(), stage.unwrap_or_default()),
+            entity,
+            prefix: (prefix.is_none(), prefix.unwrap_or_default()),
+        }
+    }
+}
+
+/// Leading digits of a file name, e.g. `10` for `10_load_orders.sql`
+pub fn numeric_prefix(file_name: &str) -> Option<u64> {
+    let digits: String = file_name
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Compare paths component-wise so `a/b.sql` sorts before `a-b.sql` on every platform
+fn compare_paths(a: &str, b: &str) -> Ordering {
+    Path::new(a).cmp(Path::new(b))
+}
+
+/// Sort scanned file paths in place according to `order`.
+///
+/// The path is always the final tie-breaker, so the result does not depend
+/// on the order in which the filesystem yielded the entries.
+pub fn sort_paths(paths: &mut [String], order: SortOrder, classifier: &FileClassifier) {
+    match order {
+        SortOrder::Path => paths.sort_by(|a, b| compare_paths(a, b)),
+        SortOrder::Stage => {
+            // classify each path once instead of on every comparison
+            let mut keyed: Vec<(StageKey, String)> = paths
+                .iter()
+                .map(|p| (StageKey::for_path(classifier, p), p.clone()))
+                .collect();
+            keyed.sort_by(|(ka, pa), (kb, pb)| ka.cmp(kb).then_with(|| compare_paths(pa, pb)));
+            for (slot, (_, path)) in paths.iter_mut().zip(keyed) {
+                *slot = path;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(paths: &[&str], order: SortOrder) -> Vec<String> {
+        let classifier = FileClassifier::default().with_base_path("base");
+        let mut paths: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+        sort_paths(&mut paths, order, &classifier);
+        paths
+    }
+
+    #[test]
+    fn test_numeric_prefix() {
+        assert_eq!(numeric_prefix("10_load.sql"), Some(10));
+        assert_eq!(numeric_prefix("002.sql"), Some(2));
+        assert_eq!(numeric_prefix("load.sql"), None);
+    }
+
+    #[test]
+    fn test_sort_by_path() {
+        let result = sorted(
+            &["base/b.sql", "base/a-b.sql", "base/a/b.sql"],
+            SortOrder::Path,
+        );
+        assert_eq!(result, ["base/a/b.sql", "base/a-b.sql", "base/b.sql"]);
+    }
+
+    #[test]
+    fn test_sort_by_stage() {
+        let result = sorted(
+            &[
+                "base/shared.sql",
+                "base/orders/03_load/pg/01_orders.sql",
+                "base/customers/02_transform/duck/10_merge.sql",
+                "base/customers/02_transform/duck/2_clean.sql",
+                "base/customers/02_transform/duck/notes.sql",
+                "base/orders/01_extract/pg/a.sql",
+                "base/customers/01_extract/pg/z.sql",
+            ],
+            SortOrder::Stage,
+        );
+        assert_eq!(
+            result,
+            [
+                "base/customers/01_extract/pg/z.sql",
+                "base/orders/01_extract/pg/a.sql",
+                "base/customers/02_transform/duck/2_clean.sql",
+                "base/customers/02_transform/duck/10_merge.sql",
+                "base/customers/02_transform/duck/notes.sql",
+                "base/orders/03_load/pg/01_orders.sql",
+                "base/shared.sql",
+            ]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+
+/// Worker threads the parallel stages of a scanner or manager run on.
+///
+/// `threads` of `None` uses rayon's global pool; an explicit count gets a dedicated
+/// pool of that size, built once and shared by every clone.
+#[derive(Clone, Debug, Default)]
+pub struct WorkerPool {
+    threads: Option<usize>,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl WorkerPool {
+    pub fn new(threads: Option<usize>) -> Self {
+        let pool = threads.and_then(|n| {
+            ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .ok()
+                .map(Arc::new)
+        });
+        WorkerPool { threads, pool }
+    }
+
+    /// Thread count asked for; `None` when rayon picks it
+    pub fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    /// Map `items` in parallel, keeping the input order in the output
+    pub fn ordered_map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync + Send,
+    {
+        let run = || items.par_iter().map(&f).collect();
+        match (self.threads, &self.pool) {
+            (None, _) => run(),
+            (Some(_), Some(pool)) => pool.install(run),
+            // could not spawn workers, fall back to the calling thread
+            (Some(_), None) => items.iter().map(&f).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_map_keeps_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        for threads in [None, Some(1), Some(4)] {
+            let doubled = WorkerPool::new(threads).ordered_map(&items, |x| x * 2);
+            assert_eq!(doubled, items.iter().map(|x| x * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_pool_shared_by_clones() {
+        let workers = WorkerPool::new(Some(2));
+        let clone = workers.clone();
+        assert!(Arc::ptr_eq(
+            workers.pool.as_ref().unwrap(),
+            clone.pool.as_ref().unwrap()
+        ));
+        assert_eq!(clone.threads(), Some(2));
+    }
+}
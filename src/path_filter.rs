@@ -0,0 +1,160 @@
+use anyhow::Result;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Include/exclude glob filter applied to paths relative to the scan root.
+///
+/// Patterns use `/` as separator, `*` does not cross directories and `**` does.
+/// A pattern prefixed with `!` in the include list is treated as an exclude,
+/// so gitignore-style lists such as `["**/01_extract/**", "!**/deprecated/**"]` work as-is.
+#[derive(Clone, Debug, Default)]
+pub struct PathFilter {
+    include_patterns: Vec<String>,
+    exclude_patterns: Vec<String>,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    // directory excludes (`dir/**` or `dir/`) rewritten to match the directory itself,
+    // used for pruning
+    exclude_dirs: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let mut include_patterns = Vec::new();
+        let mut exclude_patterns = Vec::new();
+        for pattern in include {
+            match pattern.strip_prefix('!') {
+                Some(negated) => exclude_patterns.push(negated.to_string()),
+                None => include_patterns.push(pattern.clone()),
+            }
+        }
+        for pattern in exclude {
+            exclude_patterns.push(pattern.trim_start_matches('!').to_string());
+        }
+
+        // only patterns that name a directory prune; `*.sql` must not drop `views.sql/`
+        let dir_patterns: Vec<String> = exclude_patterns
+            .iter()
+            .filter_map(|p| p.strip_suffix("/**").or_else(|| p.strip_suffix('/')))
+            .map(|p| p.to_string())
+            .collect();
+
+        Ok(PathFilter {
+            include: Self::build_set(&include_patterns)?,
+            exclude: Self::build_set(&exclude_patterns)?,
+            exclude_dirs: Self::build_set(&dir_patterns)?,
+            include_patterns,
+            exclude_patterns,
+        })
+    }
+
+    fn build_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+        }
+        Ok(Some(builder.build()?))
+    }
+
+    pub fn include_patterns(&self) -> &[String] {
+        &self.include_patterns
+    }
+
+    pub fn exclude_patterns(&self) -> &[String] {
+        &self.exclude_patterns
+    }
+
+    /// Whether the walker should descend into the directory at `rel_path`
+    pub fn allows_dir(&self, rel_path: &Path) -> bool {
+        if rel_path.as_os_str().is_empty() {
+            return true;
+        }
+        let rel = normalize(rel_path);
+        !self
+            .exclude_dirs
+            .as_ref()
+            .is_some_and(|set| set.is_match(&rel))
+    }
+
+    /// Whether the file at `rel_path` passes the include and exclude lists
+    pub fn allows_file(&self, rel_path: &Path) -> bool {
+        let rel = normalize(rel_path);
+        if self.exclude.as_ref().is_some_and(|set| set.is_match(&rel)) {
+            return false;
+        }
+        self.include.as_ref().is_none_or(|set| set.is_match(&rel))
+    }
+}
+
+/// Relative path with `/` separators so patterns behave the same on every OS
+fn normalize(rel_path: &Path) -> String {
+    rel_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+        let exclude: Vec<String> = exclude.iter().map(|s| s.to_string()).collect();
+        PathFilter::new(&include, &exclude).unwrap()
+    }
+
+    #[test]
+    fn test_empty_filter_allows_everything() {
+        let f = filter(&[], &[]);
+        assert!(f.allows_dir(Path::new("a/b")));
+        assert!(f.allows_file(Path::new("a/b/c.sql")));
+    }
+
+    #[test]
+    fn test_include_only_matches_files() {
+        let f = filter(&["**/01_extract/**"], &[]);
+        assert!(f.allows_dir(Path::new("customers")));
+        assert!(f.allows_file(Path::new("customers/01_extract/pg/a.sql")));
+        assert!(!f.allows_file(Path::new("customers/02_transform/pg/a.sql")));
+    }
+
+    #[test]
+    fn test_negated_include_becomes_exclude() {
+        let f = filter(&["**/*.sql", "!**/deprecated/**"], &[]);
+        assert_eq!(f.include_patterns(), ["**/*.sql"]);
+        assert_eq!(f.exclude_patterns(), ["**/deprecated/**"]);
+        assert!(!f.allows_dir(Path::new("customers/deprecated")));
+        assert!(!f.allows_file(Path::new("customers/deprecated/a.sql")));
+        assert!(f.allows_file(Path::new("customers/a.sql")));
+    }
+
+    #[test]
+    fn test_exclude_prunes_directories() {
+        let f = filter(&[], &["!**/deprecated/**", "tmp/"]);
+        assert_eq!(f.exclude_patterns(), ["**/deprecated/**", "tmp/"]);
+        assert!(!f.allows_dir(Path::new("deprecated")));
+        assert!(!f.allows_dir(Path::new("a/deprecated")));
+        assert!(!f.allows_dir(Path::new("tmp")));
+        assert!(f.allows_dir(Path::new("a/tmp")));
+    }
+
+    #[test]
+    fn test_file_excludes_do_not_prune() {
+        let f = filter(&[], &["**/*.sql"]);
+        assert!(f.allows_dir(Path::new("views.sql")));
+        assert!(f.allows_file(Path::new("views.sql/a.py")));
+        assert!(!f.allows_file(Path::new("views.sql/a.sql")));
+    }
+
+    #[test]
+    fn test_single_star_does_not_cross_directories() {
+        let f = filter(&["*.sql"], &[]);
+        assert!(f.allows_file(Path::new("a.sql")));
+        assert!(!f.allows_file(Path::new("a/b.sql")));
+    }
+}
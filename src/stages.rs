@@ -0,0 +1,159 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::aliases::AliasRegistry;
+use crate::enums::EtlStage;
+
+/// An ETL stage outside the built-in `EtlStage` table, such as Validate or Publish,
+/// registered by name with its position in the pipeline and its folder aliases
+#[pyclass(frozen, eq, hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CustomStage {
+    name: String,
+    ordinal: i64,
+    aliases: Vec<String>,
+}
+
+impl CustomStage {
+    pub fn new(name: &str, ordinal: i64, aliases: Vec<String>) -> Self {
+        CustomStage {
+            name: name.trim().to_string(),
+            ordinal,
+            aliases,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ordinal(&self) -> i64 {
+        self.ordinal
+    }
+
+    /// Folder names that stand for this stage, its own name included
+    pub fn all_aliases(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(|a| a.as_str()))
+    }
+}
+
+#[pymethods]
+impl CustomStage {
+    #[new]
+    #[pyo3(signature = (name, ordinal, aliases=None))]
+    fn py_new(name: &str, ordinal: i64, aliases: Option<Vec<String>>) -> PyResult<Self> {
+        if name.trim().is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "A custom stage needs a name",
+            ));
+        }
+        Ok(CustomStage::new(name, ordinal, aliases.unwrap_or_default()))
+    }
+
+    #[getter(name)]
+    fn name_py(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Position in the pipeline, on the scale of `EtlStage.ordinal`
+    #[getter(ordinal)]
+    fn ordinal_py(&self) -> i64 {
+        self.ordinal
+    }
+
+    #[getter]
+    fn aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+
+    fn __str__(&self) -> String {
+        self.name.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CustomStage(name='{}', ordinal={}, aliases={:?})",
+            self.name, self.ordinal, self.aliases
+        )
+    }
+}
+
+/// Stage of a task: a built-in stage or a registered custom one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Builtin(EtlStage),
+    Custom(CustomStage),
+}
+
+impl Stage {
+    /// Built-in name (`extract`) or the custom stage's name
+    pub fn name(&self) -> &str {
+        match self {
+            Stage::Builtin(stage) => stage.name(),
+            Stage::Custom(custom) => custom.name(),
+        }
+    }
+
+    /// Position used wherever tasks are ordered by stage
+    pub fn ordinal(&self) -> i64 {
+        match self {
+            Stage::Builtin(stage) => stage.ordinal(),
+            Stage::Custom(custom) => custom.ordinal,
+        }
+    }
+
+    /// Whether this is the stage given from Python; a name is resolved through
+    /// `aliases`, so user-defined aliases and custom stages match as in folders
+    pub fn matches(&self, arg: &StageArg, aliases: &AliasRegistry) -> bool {
+        match (self, arg) {
+            (Stage::Builtin(own), StageArg::Builtin(other)) => own == other,
+            (Stage::Custom(own), StageArg::Custom(other)) => own == other,
+            (_, StageArg::Name(name)) => aliases
+                .stage(name.trim())
+                .is_some_and(|stage| stage == *self),
+            _ => false,
+        }
+    }
+}
+
+impl IntoPy<PyObject> for Stage {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            Stage::Builtin(stage) => stage.into_py(py),
+            Stage::Custom(custom) => custom.into_py(py),
+        }
+    }
+}
+
+/// A stage given from Python: an `EtlStage`, a `CustomStage` or a name
+#[derive(Clone, Debug, FromPyObject)]
+pub enum StageArg {
+    Builtin(EtlStage),
+    Custom(CustomStage),
+    Name(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_ordinal() {
+        let custom = CustomStage::new("Validate", 25, vec!["qa".into()]);
+        let validate = Stage::Custom(custom.clone());
+        let transform = Stage::Builtin(EtlStage::Transform);
+        let load = Stage::Builtin(EtlStage::Load);
+        assert!(transform.ordinal() < validate.ordinal());
+        assert!(validate.ordinal() < load.ordinal());
+
+        let mut aliases = AliasRegistry::default();
+        aliases.register_stage(custom);
+        aliases.add_stage("10_stg", Stage::Builtin(EtlStage::Extract));
+        assert!(validate.matches(&StageArg::Name("QA".into()), &aliases));
+        assert!(load.matches(&StageArg::Name("03_load".into()), &aliases));
+        assert!(!load.matches(&StageArg::Name("validate".into()), &aliases));
+        let extract = Stage::Builtin(EtlStage::Extract);
+        assert!(extract.matches(&StageArg::Name("10_stg".into()), &aliases));
+        assert!(!extract.matches(&StageArg::Name("10_stg".into()), &AliasRegistry::default()));
+    }
+}
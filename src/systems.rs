@@ -0,0 +1,178 @@
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::aliases::AliasRegistry;
+use crate::enums::SystemType;
+
+/// A target system outside the built-in `SystemType` table, such as Greenplum,
+/// Trino or an S3 bucket, registered by name with its folder aliases and free-form
+/// attributes (e.g. a dialect or connection hint)
+#[pyclass(frozen, eq, hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CustomSystem {
+    name: String,
+    aliases: Vec<String>,
+    attributes: BTreeMap<String, String>,
+}
+
+impl CustomSystem {
+    pub fn new(name: &str, aliases: Vec<String>, attributes: BTreeMap<String, String>) -> Self {
+        CustomSystem {
+            name: name.trim().to_string(),
+            aliases,
+            attributes,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Folder names that stand for this system, its own name included
+    pub fn all_aliases(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(|a| a.as_str()))
+    }
+}
+
+#[pymethods]
+impl CustomSystem {
+    #[new]
+    #[pyo3(signature = (name, aliases=None, attributes=None))]
+    fn py_new(
+        name: &str,
+        aliases: Option<Vec<String>>,
+        attributes: Option<BTreeMap<String, String>>,
+    ) -> PyResult<Self> {
+        if name.trim().is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "A custom system needs a name",
+            ));
+        }
+        Ok(CustomSystem::new(
+            name,
+            aliases.unwrap_or_default(),
+            attributes.unwrap_or_default(),
+        ))
+    }
+
+    #[getter(name)]
+    fn name_py(&self) -> String {
+        self.name.clone()
+    }
+
+    #[getter]
+    fn aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+
+    #[getter]
+    fn attributes(&self) -> BTreeMap<String, String> {
+        self.attributes.clone()
+    }
+
+    fn __str__(&self) -> String {
+        self.name.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CustomSystem(name='{}', aliases={:?})",
+            self.name, self.aliases
+        )
+    }
+}
+
+/// System a task targets: a built-in type or a registered custom system
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum System {
+    Builtin(SystemType),
+    Custom(CustomSystem),
+}
+
+impl System {
+    /// Built-in name (`postgres`) or the custom system's name
+    pub fn name(&self) -> &str {
+        match self {
+            System::Builtin(system_type) => system_type.name(),
+            System::Custom(custom) => custom.name(),
+        }
+    }
+
+    /// Whether this is the system given from Python; a name is resolved through
+    /// `aliases`, so user-defined aliases and custom systems match as in folders
+    pub fn matches(&self, arg: &SystemArg, aliases: &AliasRegistry) -> bool {
+        match (self, arg) {
+            (System::Builtin(own), SystemArg::Builtin(other)) => own == other,
+            (System::Custom(own), SystemArg::Custom(other)) => own == other,
+            (_, SystemArg::Name(name)) => aliases
+                .system(name.trim())
+                .is_some_and(|system| system == *self),
+            _ => false,
+        }
+    }
+}
+
+impl IntoPy<PyObject> for System {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            System::Builtin(system_type) => system_type.into_py(py),
+            System::Custom(custom) => custom.into_py(py),
+        }
+    }
+}
+
+/// A system given from Python: a `SystemType`, a `CustomSystem` or a name
+#[derive(Clone, Debug, FromPyObject)]
+pub enum SystemArg {
+    Builtin(SystemType),
+    Custom(CustomSystem),
+    Name(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_system() {
+        let trino = CustomSystem::new(" Trino ", vec!["tr".into()], BTreeMap::new());
+        assert_eq!(trino.name(), "Trino");
+        assert_eq!(trino.all_aliases().collect::<Vec<_>>(), ["Trino", "tr"]);
+        assert_eq!(System::Custom(trino).name(), "Trino");
+        assert_eq!(System::Builtin(SystemType::PostgreSQL).name(), "postgres");
+    }
+
+    #[test]
+    fn test_system_matches() {
+        let custom = CustomSystem::new("Trino", vec!["tr".into()], BTreeMap::new());
+        let trino = System::Custom(custom.clone());
+        let postgres = System::Builtin(SystemType::PostgreSQL);
+        assert!(postgres.matches(
+            &SystemArg::Builtin(SystemType::PostgreSQL),
+            &AliasRegistry::default()
+        ));
+        assert!(!postgres.matches(
+            &SystemArg::Builtin(SystemType::Other),
+            &AliasRegistry::default()
+        ));
+        assert!(trino.matches(
+            &SystemArg::Custom(custom.clone()),
+            &AliasRegistry::default()
+        ));
+        assert!(!trino.matches(
+            &SystemArg::Builtin(SystemType::Other),
+            &AliasRegistry::default()
+        ));
+
+        let mut aliases = AliasRegistry::default();
+        aliases.register_system(custom);
+        aliases.add_system("gp", postgres.clone());
+        assert!(trino.matches(&SystemArg::Name(" TR ".into()), &aliases));
+        assert!(postgres.matches(&SystemArg::Name("pg".into()), &aliases));
+        assert!(postgres.matches(&SystemArg::Name("gp".into()), &aliases));
+        assert!(!postgres.matches(&SystemArg::Name("trino".into()), &aliases));
+        assert!(!postgres.matches(&SystemArg::Name("gp".into()), &AliasRegistry::default()));
+        assert!(!trino.matches(&SystemArg::Name("tr".into()), &AliasRegistry::default()));
+    }
+}
@@ -0,0 +1,372 @@
+use anyhow::Result;
+use ignore::WalkBuilder;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::archive::{is_archive, Archive, Preload};
+use crate::change_detection::FileFingerprint;
+use crate::file_loader::read_bytes;
+use crate::file_scanner::{FileScanner, ScanError};
+use crate::virtual_tree::{entry_fingerprint, ignore_rules, normalize_prefix, MemoryTree};
+
+/// Shared handle to the backend a manager scans and reads from
+pub type SharedSource = Arc<dyn TaskSource>;
+
+/// Storage backend for task files: a directory, an archive, an in-memory tree or a
+/// Python object. The scan → classify → load pipeline only talks to this trait.
+///
+/// Paths handed out by `list` are the paths subtasks report and the keys `read` and
+/// `stat` accept. Virtual backends use relative paths with `/` separators.
+pub trait TaskSource: Send + Sync + fmt::Debug {
+    /// Every file path in the backend
+    fn list(&self) -> Result<Vec<String>>;
+
+    /// Raw content of the file at `path`; decoding is up to the loader
+    fn read(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// Up to the first `len` bytes of the file at `path`, to tell binary files apart
+    /// before the file is loaded; `None` when only reading it whole is possible
+    fn head(&self, _path: &str, _len: usize) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    /// Cheap change-detection data for `path`; `None` counts as always changed
+    fn stat(&self, path: &str) -> Option<FileFingerprint>;
+
+    /// Size of the file at `path` when it is known without reading it
+    fn size(&self, _path: &str) -> Option<u64> {
+        None
+    }
+
+    /// Folder the classifier resolves the task layout against
+    fn root(&self) -> String {
+        String::new()
+    }
+
+    /// Entries the backend had to leave out of `list`, with the reason
+    fn rejected(&self) -> Vec<ScanError> {
+        Vec::new()
+    }
+
+    /// Files that pass the scanner settings, plus the problems met on the way.
+    /// The default filters `list` below `root`, honouring ignore files read from the backend.
+    fn scan(&self, scanner: &FileScanner) -> Result<(Vec<String>, Vec<ScanError>)> {
+        let names = self.list()?;
+        let root = self.root();
+        let rules = match scanner.respect_ignore_files() {
+            true => ignore_rules(names.iter().map(|n| n.as_str()), &root, |name| {
+                self.read(name)
+                    .ok()
+                    .map(|data| String::from_utf8_lossy(&data).into_owned())
+            }),
+            false => Vec::new(),
+        };
+        let found = scanner.scan_entries(names.iter().map(|n| n.as_str()), &rules, &root);
+        Ok((found, self.rejected()))
+    }
+
+    /// A fresh view of the backend for `refresh`; `None` when this one stays current
+    fn reload(&self) -> Result<Option<SharedSource>> {
+        Ok(None)
+    }
+
+    /// Path on disk to watch for changes, if the backend has one
+    fn watch_path(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Open the backend for `base_path`: an archive (optionally only below `archive_prefix`)
+/// or a directory. Only archive entries `scanner` could pick that are within
+/// `max_file_size` are read up front.
+pub fn open_path(
+    py: Python<'_>,
+    base_path: &str,
+    archive_prefix: Option<&str>,
+    scanner: &FileScanner,
+    max_file_size: Option<u64>,
+) -> PyResult<SharedSource> {
+    if !is_archive(base_path) {
+        if archive_prefix.is_some() {
+            return Err(PyValueError::new_err(format!(
+                "archive_prefix requires a .zip, .tar or .tar.gz archive, got {base_path}"
+            )));
+        }
+        return Ok(Arc::new(LocalSource::new(base_path)));
+    }
+    let preload = Preload::new(scanner.extension_set(), max_file_size);
+    py.allow_threads(|| ArchiveSource::open(base_path, archive_prefix, preload))
+        .map(|source| Arc::new(source) as SharedSource)
+        .map_err(|e| PyRuntimeError::new_err(format!("{e:#}")))
+}
+
+/// Files below a directory on disk
+#[derive(Debug)]
+pub struct LocalSource {
+    base_dir: String,
+}
+
+impl LocalSource {
+    pub fn new(base_dir: &str) -> Self {
+        LocalSource {
+            base_dir: base_dir.to_string(),
+        }
+    }
+}
+
+impl TaskSource for LocalSource {
+    fn list(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        for entry in WalkBuilder::new(&self.base_dir)
+            .standard_filters(false)
+            .build()
+        {
+            let entry = entry?;
+            if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                files.push(entry.path().to_string_lossy().to_string());
+            }
+        }
+        Ok(files)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        read_bytes(path)
+    }
+
+    fn head(&self, path: &str, len: usize) -> Result<Option<Vec<u8>>> {
+        let mut data = Vec::with_capacity(len);
+        fs::File::open(path)?
+            .take(len as u64)
+            .read_to_end(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn stat(&self, path: &str) -> Option<FileFingerprint> {
+        FileFingerprint::of(path)
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        fs::metadata(path).ok().map(|meta| meta.len())
+    }
+
+    fn root(&self) -> String {
+        self.base_dir.clone()
+    }
+
+    // the directory walker prunes excluded folders and applies ignore files as it goes
+    fn scan(&self, scanner: &FileScanner) -> Result<(Vec<String>, Vec<ScanError>)> {
+        Ok(scanner.scan(&self.base_dir))
+    }
+
+    fn watch_path(&self) -> Option<&str> {
+        Some(&self.base_dir)
+    }
+}
+
+/// Entries of a `.zip` / `.tar` / `.tar.gz` archive, optionally only below an inner prefix
+#[derive(Debug)]
+pub struct ArchiveSource {
+    archive: Archive,
+    prefix: String,
+    preload: Preload,
+}
+
+impl ArchiveSource {
+    pub fn open(path: &str, prefix: Option<&str>, preload: Preload) -> Result<Self> {
+        Ok(ArchiveSource {
+            archive: Archive::open(path, &preload)?,
+            prefix: normalize_prefix(prefix),
+            preload,
+        })
+    }
+}
+
+impl TaskSource for ArchiveSource {
+    fn list(&self) -> Result<Vec<String>> {
+        Ok(self.archive.files().map(|f| f.to_string()).collect())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.archive.read(path)
+    }
+
+    fn head(&self, path: &str, len: usize) -> Result<Option<Vec<u8>>> {
+        let mut data = self.archive.read(path)?;
+        data.truncate(len);
+        Ok(Some(data))
+    }
+
+    fn stat(&self, path: &str) -> Option<FileFingerprint> {
+        self.archive.fingerprint(path)
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        self.archive.size(path)
+    }
+
+    fn root(&self) -> String {
+        self.prefix.clone()
+    }
+
+    fn rejected(&self) -> Vec<ScanError> {
+        rejected_errors(
+            self.archive.rejected(),
+            "entry path points outside the archive",
+        )
+    }
+
+    // the archive file may have been replaced since it was read
+    fn reload(&self) -> Result<Option<SharedSource>> {
+        let source = ArchiveSource::open(
+            self.archive.path(),
+            Some(&self.prefix),
+            self.preload.clone(),
+        )?;
+        Ok(Some(Arc::new(source)))
+    }
+
+    fn watch_path(&self) -> Option<&str> {
+        Some(self.archive.path())
+    }
+}
+
+impl TaskSource for MemoryTree {
+    fn list(&self) -> Result<Vec<String>> {
+        Ok(self.files().map(|f| f.to_string()).collect())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        MemoryTree::read(self, path)
+    }
+
+    fn head(&self, path: &str, len: usize) -> Result<Option<Vec<u8>>> {
+        let data = MemoryTree::read(self, path)?;
+        Ok(Some(data[..data.len().min(len)].to_vec()))
+    }
+
+    fn stat(&self, path: &str) -> Option<FileFingerprint> {
+        self.fingerprint(path)
+    }
+
+    fn rejected(&self) -> Vec<ScanError> {
+        rejected_errors(MemoryTree::rejected(self), "path points outside the tree")
+    }
+}
+
+/// A Python object implementing the task source protocol:
+///
+/// - `list() -> Iterable[str]`: every file path
+/// - `read(path) -> str | bytes`: file content
+/// - `stat(path) -> tuple[int, float | None] | None` (optional): size and mtime in
+///   seconds; without it the content is hashed for change detection
+/// - `root: str` (optional attribute): folder the layout is resolved against
+#[derive(Debug)]
+pub struct PySource {
+    object: PyObject,
+}
+
+impl PySource {
+    pub fn new(object: PyObject) -> Self {
+        PySource { object }
+    }
+
+    // Python failures surface as I/O errors of the backend
+    fn py_error(py: Python<'_>, err: PyErr) -> anyhow::Error {
+        io::Error::other(err.value_bound(py).to_string()).into()
+    }
+
+    fn has_stat(&self, py: Python<'_>) -> bool {
+        self.object.bind(py).hasattr("stat").unwrap_or(false)
+    }
+
+    // `(size, mtime)` from the optional `stat` method; `None` when it fails or returns None
+    fn call_stat(&self, py: Python<'_>, path: &str) -> Option<(u64, Option<f64>)> {
+        let stat = self.object.call_method1(py, "stat", (path,)).ok()?;
+        stat.extract::<Option<(u64, Option<f64>)>>(py).ok()?
+    }
+
+    fn read_bytes(&self, py: Python<'_>, path: &str) -> Result<Vec<u8>> {
+        let value = self
+            .object
+            .call_method1(py, "read", (path,))
+            .map_err(|e| Self::py_error(py, e))?;
+        let value = value.bind(py);
+        if let Ok(bytes) = value.downcast::<PyBytes>() {
+            return Ok(bytes.as_bytes().to_vec());
+        }
+        let text: String = value.extract().map_err(|e| Self::py_error(py, e))?;
+        Ok(text.into_bytes())
+    }
+}
+
+impl TaskSource for PySource {
+    fn list(&self) -> Result<Vec<String>> {
+        Python::with_gil(|py| {
+            let names = self
+                .object
+                .call_method0(py, "list")
+                .map_err(|e| Self::py_error(py, e))?;
+            let mut files = Vec::new();
+            for name in names.bind(py).iter().map_err(|e| Self::py_error(py, e))? {
+                let name = name.map_err(|e| Self::py_error(py, e))?;
+                files.push(
+                    name.extract::<String>()
+                        .map_err(|e| Self::py_error(py, e))?,
+                );
+            }
+            Ok(files)
+        })
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        Python::with_gil(|py| self.read_bytes(py, path))
+    }
+
+    fn stat(&self, path: &str) -> Option<FileFingerprint> {
+        Python::with_gil(|py| {
+            if !self.has_stat(py) {
+                let data = self.read_bytes(py, path).ok()?;
+                return Some(entry_fingerprint(&data, None));
+            }
+            let (len, mtime) = self.call_stat(py, path)?;
+            let modified = mtime
+                .filter(|t| t.is_finite() && *t >= 0.0)
+                .map(|t| SystemTime::UNIX_EPOCH + Duration::from_secs_f64(t));
+            Some(FileFingerprint::new(len, modified, None))
+        })
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        Python::with_gil(|py| match self.has_stat(py) {
+            true => self.call_stat(py, path).map(|(len, _)| len),
+            false => None,
+        })
+    }
+
+    fn root(&self) -> String {
+        Python::with_gil(|py| {
+            self.object
+                .bind(py)
+                .getattr("root")
+                .and_then(|root| root.extract::<String>())
+                .map(|root| normalize_prefix(Some(&root)))
+                .unwrap_or_default()
+        })
+    }
+}
+
+fn rejected_errors(rejected: &[String], reason: &str) -> Vec<ScanError> {
+    rejected
+        .iter()
+        .map(|name| ScanError {
+            path: name.clone(),
+            reason: reason.to_string(),
+        })
+        .collect()
+}
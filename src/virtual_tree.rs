@@ -0,0 +1,216 @@
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+use crate::change_detection::FileFingerprint;
+use crate::file_scanner::SUBTASK_IGNORE_FILENAME;
+
+/// Ignore files honoured inside archives and in-memory trees, lowest precedence first
+pub const IGNORE_FILENAMES: [&str; 3] = [".gitignore", ".ignore", SUBTASK_IGNORE_FILENAME];
+
+/// Entry path as `a/b/c`, or `None` if it is absolute or climbs out of the tree
+pub fn entry_name(path: &Path) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+/// Inner prefix normalized to `a/b` form; empty for the tree root
+pub fn normalize_prefix(prefix: Option<&str>) -> String {
+    prefix.unwrap_or_default().trim_matches('/').to_string()
+}
+
+/// Fingerprint of an entry held in memory; such entries often carry no usable mtime,
+/// so the content is hashed. The hash is a CRC-32, stable across builds for the index
+/// cache and the same a zip header records for entries that are not loaded.
+pub fn entry_fingerprint(data: &[u8], modified: Option<SystemTime>) -> FileFingerprint {
+    let hash = crc32fast::hash(data);
+    FileFingerprint::new(data.len() as u64, modified, Some(u64::from(hash)))
+}
+
+/// Gitignore-style rules from the ignore files among `names` below `root`, ordered so
+/// that deeper files (and `.subtaskignore` within one directory) are consulted first
+pub fn ignore_rules<'a, F>(
+    names: impl Iterator<Item = &'a str>,
+    root: &str,
+    read: F,
+) -> Vec<Gitignore>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut found: Vec<(usize, usize, &str)> = Vec::new();
+    for name in names {
+        let path = Path::new(name);
+        if !root.is_empty() && !path.starts_with(root) {
+            continue;
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(rank) = IGNORE_FILENAMES.iter().position(|f| *f == file_name) {
+            found.push((path.components().count(), rank, name));
+        }
+    }
+    found.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+
+    found
+        .into_iter()
+        .filter_map(|(_, _, name)| {
+            let dir = Path::new(name).parent().unwrap_or(Path::new(""));
+            let mut builder = GitignoreBuilder::new(dir);
+            for line in read(name)?.lines() {
+                let _ = builder.add_line(None, line);
+            }
+            builder.build().ok()
+        })
+        .collect()
+}
+
+/// Whether `rules` (from `ignore_rules`) exclude the entry at `name`
+pub fn is_ignored(rules: &[Gitignore], name: &str) -> bool {
+    let path = Path::new(name);
+    for rules in rules {
+        if !path.starts_with(rules.path()) {
+            continue;
+        }
+        let matched = rules.matched_path_or_any_parents(path, false);
+        if matched.is_ignore() {
+            return true;
+        }
+        if matched.is_whitelist() {
+            return false;
+        }
+    }
+    false
+}
+
+/// Task files held in memory, keyed by relative path with `/` separators.
+///
+/// Keys that are absolute or climb out of the tree with `..` are left out and listed
+/// in `rejected`.
+#[derive(Debug, Default)]
+pub struct MemoryTree {
+    entries: BTreeMap<String, Vec<u8>>,
+    rejected: Vec<String>,
+}
+
+impl MemoryTree {
+    pub fn new(files: impl IntoIterator<Item = (String, Vec<u8>)>) -> Self {
+        let mut tree = MemoryTree::default();
+        for (key, data) in files {
+            match entry_name(Path::new(&key.replace('\\', "/"))) {
+                Some(name) => {
+                    tree.entries.insert(name, data);
+                }
+                None => tree.rejected.push(key),
+            }
+        }
+        tree.rejected.sort();
+        tree
+    }
+
+    /// Relative paths of every file, sorted
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|k| k.as_str())
+    }
+
+    /// Keys that were skipped because they point outside the tree
+    pub fn rejected(&self) -> &[String] {
+        &self.rejected
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>> {
+        match self.entries.get(name) {
+            Some(data) => Ok(data.clone()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{name} not found in the in-memory tree"),
+            )
+            .into()),
+        }
+    }
+
+    pub fn fingerprint(&self, name: &str) -> Option<FileFingerprint> {
+        self.entries
+            .get(name)
+            .map(|data| entry_fingerprint(data, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_name() {
+        assert_eq!(
+            entry_name(Path::new("./tasks/a.sql")).as_deref(),
+            Some("tasks/a.sql")
+        );
+        assert_eq!(entry_name(Path::new("../a.sql")), None);
+        assert_eq!(entry_name(Path::new("/etc/a.sql")), None);
+    }
+
+    #[test]
+    fn test_normalize_prefix() {
+        assert_eq!(normalize_prefix(Some("/tasks/etl/")), "tasks/etl");
+        assert_eq!(normalize_prefix(None), "");
+    }
+
+    #[test]
+    fn test_entry_fingerprint_is_stable() {
+        assert_eq!(
+            entry_fingerprint(b"hello", None),
+            FileFingerprint::new(5, None, Some(0x3610_a686))
+        );
+    }
+
+    #[test]
+    fn test_memory_tree() {
+        let tree = MemoryTree::new([
+            ("b\\c.sql".to_string(), b"SELECT 1;".to_vec()),
+            ("./a.sql".to_string(), b"SELECT 2;".to_vec()),
+            ("../x.sql".to_string(), Vec::new()),
+            ("bad.sql".to_string(), vec![0xff, 0xfe]),
+        ]);
+        assert_eq!(
+            tree.files().collect::<Vec<_>>(),
+            ["a.sql", "b/c.sql", "bad.sql"]
+        );
+        assert_eq!(tree.rejected(), ["../x.sql"]);
+        assert_eq!(tree.read("b/c.sql").unwrap(), b"SELECT 1;");
+        assert_eq!(tree.read("bad.sql").unwrap(), [0xff, 0xfe]);
+        assert!(tree.read("missing.sql").is_err());
+        assert_ne!(tree.fingerprint("a.sql"), tree.fingerprint("b/c.sql"));
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let tree = MemoryTree::new([
+            (".gitignore".to_string(), b"*.tmp.sql\n".to_vec()),
+            (
+                "etl/.subtaskignore".to_string(),
+                b"scratch/\n!keep.tmp.sql\n".to_vec(),
+            ),
+        ]);
+        let rules = ignore_rules(tree.files(), "", |name| {
+            tree.read(name)
+                .ok()
+                .map(|d| String::from_utf8_lossy(&d).into_owned())
+        });
+        assert!(is_ignored(&rules, "a.tmp.sql"));
+        assert!(is_ignored(&rules, "etl/scratch/a.sql"));
+        assert!(!is_ignored(&rules, "etl/keep.tmp.sql"));
+        assert!(!is_ignored(&rules, "etl/a.sql"));
+    }
+}
@@ -0,0 +1,244 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::build_subtask;
+use crate::change_detection::{ChangeSet, FileFingerprint};
+use crate::decoding::TextDecoder;
+use crate::enums::ChangeKind;
+use crate::errors::SubtaskError;
+use crate::file_classifier::FileClassifier;
+use crate::file_scanner::FileScanner;
+use crate::models::Subtask;
+use crate::task_source::SharedSource;
+
+/// One change seen while watching, already classified
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct SubtaskEvent {
+    #[pyo3(get)]
+    pub kind: ChangeKind,
+    #[pyo3(get)]
+    pub path: String,
+    /// Classified task for added and modified files; `None` for removals and broken files
+    #[pyo3(get)]
+    pub subtask: Option<Subtask>,
+    /// Why an added or modified file could not be classified
+    #[pyo3(get)]
+    pub error: Option<SubtaskError>,
+}
+
+#[pymethods]
+impl SubtaskEvent {
+    fn __repr__(&self) -> String {
+        format!(
+            "SubtaskEvent(kind={}, path='{}')",
+            self.kind.__repr__(),
+            self.path
+        )
+    }
+}
+
+/// Turn a change set into events, in added, modified, removed order.
+/// `lookup` gives the classified subtask (or the reason there is none) for a path.
+pub fn events_for<F>(changes: &ChangeSet, mut lookup: F) -> Vec<SubtaskEvent>
+where
+    F: FnMut(&str) -> Result<Subtask, SubtaskError>,
+{
+    let mut events = Vec::new();
+    for (kind, paths) in [
+        (ChangeKind::Added, &changes.added),
+        (ChangeKind::Modified, &changes.modified),
+    ] {
+        for path in paths {
+            let (subtask, error) = match lookup(path) {
+                Ok(subtask) => (Some(subtask), None),
+                Err(error) => (None, Some(error)),
+            };
+            events.push(SubtaskEvent {
+                kind,
+                path: path.clone(),
+                subtask,
+                error,
+            });
+        }
+    }
+    events.extend(changes.removed.iter().map(|path| SubtaskEvent {
+        kind: ChangeKind::Removed,
+        path: path.clone(),
+        subtask: None,
+        error: None,
+    }));
+    events
+}
+
+/// Recursive filesystem watch on a directory (inotify on Linux) with debouncing
+pub struct FsWatch {
+    _watcher: RecommendedWatcher, // dropping it stops the watch
+    events: Receiver<notify::Result<notify::Event>>,
+    debounce: Duration,
+}
+
+impl FsWatch {
+    pub fn new(base_dir: &str, debounce: Duration) -> notify::Result<Self> {
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(Path::new(base_dir), RecursiveMode::Recursive)?;
+        Ok(FsWatch {
+            _watcher: watcher,
+            events,
+            debounce,
+        })
+    }
+
+    /// Wait up to `timeout` (forever for `None`) for filesystem activity, then until it has
+    /// been quiet for the debounce interval, so a burst of writes counts once.
+    /// Returns `Some(false)` on timeout and `None` once the watch is gone.
+    pub fn wait(&self, timeout: Option<Duration>) -> Option<bool> {
+        let first = match timeout {
+            Some(timeout) => self.events.recv_timeout(timeout),
+            None => self
+                .events
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match first {
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => return Some(false),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+        loop {
+            match self.events.recv_timeout(self.debounce) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => return Some(true),
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+/// Blocking iterator over debounced batches of classified changes in a task source.
+///
+/// Each batch rescans with the scanner's settings, so ignore files and globs apply to
+/// watched changes the same way they apply to a normal scan. Batches are never empty.
+pub struct SubtaskEvents {
+    watch: FsWatch,
+    scanner: FileScanner,
+    classifier: FileClassifier,
+    source: SharedSource,
+    decoder: Arc<TextDecoder>,
+    paths: Vec<String>,
+    fingerprints: HashMap<String, Option<FileFingerprint>>,
+}
+
+impl SubtaskEvents {
+    /// Start watching `source`, which must have a path on disk; files present now are the
+    /// baseline and produce no events
+    pub fn new(
+        scanner: FileScanner,
+        classifier: FileClassifier,
+        source: SharedSource,
+        decoder: Arc<TextDecoder>,
+        debounce: Duration,
+    ) -> notify::Result<Self> {
+        let Some(watch_path) = source.watch_path() else {
+            return Err(notify::Error::generic("task source has no path to watch"));
+        };
+        // watch before the baseline scan so nothing written in between is lost
+        let watch = FsWatch::new(watch_path, debounce)?;
+        let mut events = SubtaskEvents {
+            watch,
+            scanner,
+            classifier,
+            source,
+            decoder,
+            paths: Vec::new(),
+            fingerprints: HashMap::new(),
+        };
+        events.rescan();
+        Ok(events)
+    }
+
+    // Rescan the source and return what changed since the previous scan.
+    // A source that cannot be read (e.g. an archive being rewritten) counts as unchanged.
+    fn rescan(&mut self) -> ChangeSet {
+        let source = match self.source.reload() {
+            Ok(Some(source)) => source,
+            Ok(None) => self.source.clone(),
+            Err(_) => return ChangeSet::default(),
+        };
+        let Ok((paths, _)) = self.scanner.scan_sorted(source.as_ref(), &self.classifier) else {
+            return ChangeSet::default();
+        };
+        let fingerprints: Vec<(String, Option<FileFingerprint>)> =
+            paths.iter().map(|p| (p.clone(), source.stat(p))).collect();
+        let changes = ChangeSet::detect(&self.paths, &self.fingerprints, &fingerprints);
+        self.paths = paths;
+        self.fingerprints = fingerprints.into_iter().collect();
+        self.source = source;
+        changes
+    }
+}
+
+impl Iterator for SubtaskEvents {
+    type Item = Vec<SubtaskEvent>;
+
+    /// Block until the next batch of changes; `None` once the watch has ended
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.watch.wait(None)?;
+            let changes = self.rescan();
+            if changes.is_empty() {
+                continue;
+            }
+            // classified and size-checked like a load; contents are read on first access
+            return Some(events_for(&changes, |path| {
+                let fingerprint = self.fingerprints.get(path).and_then(|f| f.as_ref());
+                build_subtask(
+                    &self.classifier,
+                    &self.source,
+                    &self.decoder,
+                    None,
+                    true,
+                    path,
+                    fingerprint,
+                )
+                .loaded
+                .map_err(|e| SubtaskError::from_anyhow(path, &e))
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_for_order() {
+        let changes = ChangeSet {
+            added: vec!["a".to_string()],
+            removed: vec!["r".to_string()],
+            modified: vec!["m".to_string()],
+        };
+        let events = events_for(&changes, |path| {
+            if path == "m" {
+                Err(SubtaskError::new(path, crate::enums::ErrorKind::Io, "gone"))
+            } else {
+                Ok(Subtask::new(path))
+            }
+        });
+        let kinds: Vec<ChangeKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [ChangeKind::Added, ChangeKind::Modified, ChangeKind::Removed]
+        );
+        assert!(events[0].subtask.is_some());
+        assert!(events[1].error.is_some());
+        assert!(events[2].subtask.is_none() && events[2].error.is_none());
+    }
+}
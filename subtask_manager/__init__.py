@@ -1,10 +1,24 @@
 from ._core import (
+    AliasRegistry,
+    ChangeKind,
+    ChangeSet,
+    CustomStage,
+    CustomSystem,
+    ErrorKind,
+    ErrorPolicy,
     EtlStage,
+    Explanation,
     FileClassifier,
     FileScanner,
     ParamType,
     RenderedSubtask,
+    ScanError,
+    SegmentMatch,
+    SegmentRole,
+    SortOrder,
     Subtask,
+    SubtaskError,
+    SubtaskEvent,
     SubtaskManager,
     SystemType,
     TaskType,
@@ -18,12 +32,26 @@ def main() -> int:
 
 
 __all__ = [
+    "AliasRegistry",
+    "ChangeKind",
+    "ChangeSet",
+    "CustomStage",
+    "CustomSystem",
+    "ErrorKind",
+    "ErrorPolicy",
     "EtlStage",
+    "Explanation",
     "FileClassifier",
     "FileScanner",
     "ParamType",
     "RenderedSubtask",
+    "ScanError",
+    "SegmentMatch",
+    "SegmentRole",
+    "SortOrder",
     "Subtask",
+    "SubtaskError",
+    "SubtaskEvent",
     "SubtaskManager",
     "SystemType",
     "TaskType",
@@ -1,7 +1,13 @@
 from pathlib import Path
-from typing import Any
+from typing import Any, Callable
 
-from typing_extensions import override
+from typing_extensions import TypeAlias, override
+
+Aliases: TypeAlias = (
+    "AliasRegistry | dict[str, Any] | str | Path"
+)
+Stage: TypeAlias = "EtlStage | CustomStage"
+System: TypeAlias = "SystemType | CustomSystem"
 
 class TaskType:
     id: int
@@ -48,7 +54,11 @@ class SystemType:
 
     def __init__(self, *args: object, **kwargs: object) -> None: ...
     @classmethod
-    def from_alias(cls, alias: str) -> "SystemType": ...
+    def from_alias(
+        cls, alias: str, aliases: "AliasRegistry | None" = None
+    ) -> "System":
+        """Built-in system for an alias; with `aliases`, may be a CustomSystem."""
+        ...
     @override
     def __str__(self) -> str: ...
     @override
@@ -64,6 +74,8 @@ class EtlStage:
     id: int
     name: str
     aliases: list[str]
+    ordinal: int
+    """Position in the pipeline (id * 10), shared with custom stages."""
 
     Setup: "EtlStage"
     Extract: "EtlStage"
@@ -75,7 +87,9 @@ class EtlStage:
 
     def __init__(self, *args: object, **kwargs: object) -> None: ...
     @classmethod
-    def from_alias(cls, alias: str) -> "EtlStage": ...
+    def from_alias(cls, alias: str, aliases: "AliasRegistry | None" = None) -> "Stage":
+        """Built-in stage for an alias; with `aliases`, may be a CustomStage."""
+        ...
     @override
     def __str__(self) -> str: ...
     @override
@@ -115,6 +129,135 @@ class ParamType:
     @override
     def __hash__(self) -> int: ...
 
+class SortOrder:
+    id: int
+    name: str
+    aliases: list[str]
+
+    Path: "SortOrder"
+    Stage: "SortOrder"
+
+    def __init__(self, *args: object, **kwargs: object) -> None: ...
+    @classmethod
+    def from_alias(cls, alias: str) -> "SortOrder": ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __ne__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+
+class ErrorPolicy:
+    """What SubtaskManager does with files that cannot be classified or loaded."""
+
+    id: int
+    name: str
+    aliases: list[str]
+
+    Raise: "ErrorPolicy"
+    Skip: "ErrorPolicy"
+    Collect: "ErrorPolicy"
+
+    def __init__(self, *args: object, **kwargs: object) -> None: ...
+    @classmethod
+    def from_alias(cls, alias: str) -> "ErrorPolicy": ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __ne__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+
+class ErrorKind:
+    id: int
+    name: str
+
+    FolderStructure: "ErrorKind"
+    UnknownTaskType: "ErrorKind"
+    Io: "ErrorKind"
+    Encoding: "ErrorKind"
+    Other: "ErrorKind"
+    TooLarge: "ErrorKind"
+    Binary: "ErrorKind"
+    Ambiguous: "ErrorKind"
+    Header: "ErrorKind"
+
+    def __init__(self, *args: object, **kwargs: object) -> None: ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __ne__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+
+class ChangeKind:
+    id: int
+    name: str
+
+    Added: "ChangeKind"
+    Modified: "ChangeKind"
+    Removed: "ChangeKind"
+
+    def __init__(self, *args: object, **kwargs: object) -> None: ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __ne__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+
+class SegmentRole:
+    """What the classifier used a folder of a task path for."""
+
+    id: int
+    name: str
+
+    Stage: "SegmentRole"
+    System: "SegmentRole"
+    Entity: "SegmentRole"
+    Ignored: "SegmentRole"
+    Unmatched: "SegmentRole"
+
+    def __init__(self, *args: object, **kwargs: object) -> None: ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __ne__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+
+class SubtaskError:
+    """A file that could not be classified or loaded."""
+
+    path: str
+    kind: ErrorKind
+    message: str
+
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __str__(self) -> str: ...
+
 class RenderedSubtask:
     """Lightweight structure containing only rendered values after parameter application."""
 
@@ -122,6 +265,8 @@ class RenderedSubtask:
     path: str
     command: str | None
     params: dict[str, str]
+    content_hash: str | None
+    """Hex SHA-256 of the rendered command."""
 
     @override
     def __repr__(self) -> str: ...
@@ -132,23 +277,41 @@ class Subtask:
     original_name: str
     original_path: str
 
-    stage: EtlStage | None
+    stage: "Stage | None"
+    """An EtlStage member or the registered CustomStage of the task."""
     entity: str | None
-    system_type: SystemType | None
+    """Entity folders joined with `/`, e.g. `finance/billing`."""
+    entity_path: list[str]
+    """Entity folders from the outermost in; empty when there is no entity."""
+    system_type: "System | None"
+    """A SystemType member or the registered CustomSystem the task targets."""
     task_type: TaskType | None
     is_common: bool
 
     name: str
     path: str
     command: str | None
+    """File content; read from disk on first access for lazily loaded subtasks."""
     rendered_command: str | None
     params: set[str] | None
+    is_loaded: bool
+    encoding: str | None
+    """Encoding the file was decoded from, e.g. `utf-8` or `windows-1251`."""
+    size_bytes: int | None
+    """Size of the source file when it was scanned."""
+    metadata: dict[str, str]
+    """Header entries other than stage, system and entity, e.g. `{"tags": "daily"}`;
+    empty unless headers are read."""
+    modified_at: float | None
+    """Modification time of the source file when it was scanned (seconds since the epoch)."""
+    content_hash: str | None
+    """Hex SHA-256 of the template command; reads the file on first access."""
 
     def __init__(
         self,
-        stage: EtlStage | None = None,
+        stage: "Stage | str | None" = None,
         entity: str | None = None,
-        system_type: SystemType | None = None,
+        system_type: "System | str | None" = None,
         task_type: TaskType | None = None,
         is_common: bool = False,
         name: str = "",
@@ -178,6 +341,12 @@ class Subtask:
         ...
 
     def get_stored_params(self) -> dict[str, str]: ...
+    def release(self) -> None:
+        """
+        Drop the lazily read file content; it is read again on next access.
+        """
+        ...
+
     def get_command(self) -> str | None:
         """
         Get the command to execute. Returns rendered_command if available, otherwise command template.
@@ -209,47 +378,263 @@ class Subtask:
         """
         ...
 
+class ChangeSet:
+    """Files added, removed or modified between two scans."""
+
+    added: list[str]
+    removed: list[str]
+    modified: list[str]
+
+    def __bool__(self) -> bool: ...
+    @override
+    def __repr__(self) -> str: ...
+
+class SubtaskEvent:
+    """A change seen by `SubtaskManager.watch`, already classified."""
+
+    kind: ChangeKind
+    path: str
+    subtask: "Subtask | None"
+    error: SubtaskError | None
+
+    @override
+    def __repr__(self) -> str: ...
+
+class ScanError:
+    """A path skipped during scanning, with the reason it could not be read."""
+
+    path: str
+    reason: str
+
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __str__(self) -> str: ...
+
 class SubtaskManager:
     base_path: str
     subtasks: list[Subtask]
     file_paths: list[str]
     num_files: int
     classifier: "FileClassifier"
+    include: list[str]
+    exclude: list[str]
+    sort_by: SortOrder
+    strict: bool
+    follow_symlinks: bool
+    include_hidden: bool
+    max_depth: int | None
+    threads: int | None
+    scan_errors: list[ScanError]
+    on_error: ErrorPolicy
+    errors: list[SubtaskError]
+    classification_warnings: list[SubtaskError]
+    """Ambiguous folders of the loaded subtasks (`ErrorKind.Ambiguous`); empty under
+    `strict_classification`, where such files fail instead."""
+    read_headers: bool
+    lazy: bool
+    archive_prefix: str | None
+    fallback_encodings: list[str]
+    normalize_newlines: bool
+    max_file_size: int | None
+    detect_binary: bool
+    cache_path: str | None
+
+    def __init__(
+        self,
+        base_path: str | Path,
+        respect_ignore_files: bool = True,
+        include: list[str] | None = None,
+        exclude: list[str] | None = None,
+        sort_by: SortOrder = SortOrder.Path,
+        strict: bool = False,
+        follow_symlinks: bool = False,
+        include_hidden: bool = True,
+        max_depth: int | None = None,
+        threads: int | None = None,
+        on_error: ErrorPolicy = ErrorPolicy.Raise,
+        lazy: bool = True,
+        archive_prefix: str | None = None,
+        fallback_encodings: list[str] | None = None,
+        normalize_newlines: bool = False,
+        max_file_size: int | None = None,
+        detect_binary: bool = True,
+        layout: str | None = None,
+        max_entity_depth: int | None = 1,
+        aliases: "Aliases | None" = None,
+        cache_path: str | Path | None = None,
+        strict_classification: bool = False,
+        read_headers: bool = False,
+    ) -> None: ...
+    @staticmethod
+    def from_dict(
+        files: dict[str, str | bytes],
+        respect_ignore_files: bool = True,
+        include: list[str] | None = None,
+        exclude: list[str] | None = None,
+        sort_by: SortOrder = SortOrder.Path,
+        strict: bool = False,
+        include_hidden: bool = True,
+        max_depth: int | None = None,
+        threads: int | None = None,
+        on_error: ErrorPolicy = ErrorPolicy.Raise,
+        lazy: bool = True,
+        fallback_encodings: list[str] | None = None,
+        normalize_newlines: bool = False,
+        max_file_size: int | None = None,
+        detect_binary: bool = True,
+        layout: str | None = None,
+        max_entity_depth: int | None = 1,
+        aliases: "Aliases | None" = None,
+        strict_classification: bool = False,
+        read_headers: bool = False,
+    ) -> "SubtaskManager":
+        """
+        Build a manager from a mapping of relative path to content, without touching
+        the filesystem. Keys that are absolute or contain `..` are listed in `scan_errors`.
+        """
+        ...
+    @staticmethod
+    def from_source(
+        source: Any,
+        respect_ignore_files: bool = True,
+        include: list[str] | None = None,
+        exclude: list[str] | None = None,
+        sort_by: SortOrder = SortOrder.Path,
+        strict: bool = False,
+        include_hidden: bool = True,
+        max_depth: int | None = None,
+        threads: int | None = None,
+        on_error: ErrorPolicy = ErrorPolicy.Raise,
+        lazy: bool = True,
+        fallback_encodings: list[str] | None = None,
+        normalize_newlines: bool = False,
+        max_file_size: int | None = None,
+        detect_binary: bool = True,
+        layout: str | None = None,
+        max_entity_depth: int | None = 1,
+        aliases: "Aliases | None" = None,
+        strict_classification: bool = False,
+        read_headers: bool = False,
+    ) -> "SubtaskManager":
+        """
+        Build a manager over a custom task source: an object with `list()` returning
+        every file path and `read(path)` returning its content as `str` or `bytes`.
+        An optional `stat(path) -> (size, mtime) | None` makes `refresh` cheaper, and an
+        optional `root` attribute names the folder the task layout starts in.
+        """
+        ...
+    def load_all(self) -> None:
+        """
+        Classify every file and read all file contents up front.
+        """
+        ...
+
+    def refresh(self) -> ChangeSet:
+        """
+        Rescan the base directory and reload only the files that were added or modified.
+        """
+        ...
+
+    def watch(
+        self,
+        callback: Callable[[list[SubtaskEvent]], bool | None],
+        debounce_ms: int = 200,
+        timeout: float | None = None,
+    ) -> None:
+        """
+        Watch the base directory and call `callback` with each debounced batch of changes.
+        Stops when the callback returns False or after `timeout` seconds.
+        """
+        ...
+
+    def release_contents(self) -> None:
+        """
+        Drop the file contents read so far; they are read again on next access.
+        """
+        ...
 
-    def __init__(self, base_path: str | Path) -> None: ...
-    def load_all(self) -> None: ...
     def get_tasks(
         self,
-        etl_stage: EtlStage | None = None,
+        etl_stage: "Stage | str | None" = None,
         entity: str | None = None,
-        system_type: SystemType | None = None,
+        system_type: "System | str | None" = None,
         task_type: TaskType | None = None,
         is_common: bool | None = None,
         include_common: bool | None = True,
-    ) -> list[Subtask]: ...
+        entity_prefix: str | None = None,
+    ) -> list[Subtask]:
+        """
+        Subtasks matching every given filter. `entity` must match exactly, while
+        `entity_prefix` (e.g. "finance" or "finance/billing") matches whole leading
+        entity folders. `etl_stage` and `system_type` may also be custom stages and
+        systems, or names resolved through the manager's `aliases`.
+        """
+        ...
     def get_task(self, name: str, entity: str | None = None) -> Subtask: ...
 
 class FileScanner:
     """Scanner for finding files with specific extensions."""
 
-    def __init__(self, extensions: list[str]) -> None:
+    def __init__(
+        self,
+        extensions: list[str],
+        respect_ignore_files: bool = True,
+        include: list[str] | None = None,
+        exclude: list[str] | None = None,
+        sort_by: SortOrder = SortOrder.Path,
+        strict: bool = False,
+        follow_symlinks: bool = False,
+        include_hidden: bool = True,
+        max_depth: int | None = None,
+        threads: int | None = None,
+    ) -> None:
         """
         Initialize FileScanner with file extensions to search for.
 
         Args:
             extensions: List of file extensions (with or without leading dot)
+            respect_ignore_files: Skip paths matched by `.gitignore`, `.ignore`
+                and `.subtaskignore` files
+            include: Globs relative to the scanned directory; when given, only
+                matching files are returned. `!`-prefixed entries act as excludes
+            exclude: Globs relative to the scanned directory; matching files are
+                skipped, and directories matched by a `dir/**` or `dir/` pattern are
+                not descended into
+            sort_by: Order of returned paths. `SortOrder.Path` sorts by relative
+                path, `SortOrder.Stage` by stage ordinal, entity, then numeric
+                file name prefix
+            strict: Raise from `scan_files` when any path could not be walked
+            follow_symlinks: Descend into symlinked directories and read symlinked
+                files; symlink loops are reported in `scan_errors`
+            include_hidden: Include dotfiles and dot-directories
+            max_depth: Maximum depth below `base_dir` (files directly inside it
+                are at depth 1); `None` means unlimited
+            threads: Worker threads for the directory walk; `None` picks a
+                default based on the CPU count. Output order does not depend on it
+
+        Raises:
+            ValueError: If a glob pattern is invalid
         """
         ...
 
-    def scan_files(self, base_dir: str | Path) -> list[str]:
+    def scan_files(self, base_dir: str | Path, archive_prefix: str | None = None) -> list[str]:
         """
         Scan directory recursively for files with matching extensions.
 
         Args:
-            base_dir: Root directory to scan (string path or pathlib.Path)
+            base_dir: Root directory to scan (string path or pathlib.Path), or a
+                `.zip` / `.tar` / `.tar.gz` archive
+            archive_prefix: Folder inside the archive to scan; the whole archive if None
 
         Returns:
-            List of absolute file paths
+            List of file paths, ordered according to `sort_by`. Archive entries are
+            returned as archive-relative paths
+
+        Raises:
+            RuntimeError: In strict mode, if any path could not be walked, or if the
+                archive cannot be read
+            ValueError: If archive_prefix is given for a directory
         """
         ...
 
@@ -258,26 +643,228 @@ class FileScanner:
         """Get the normalized extensions this scanner searches for."""
         ...
 
+    @property
+    def respect_ignore_files(self) -> bool:
+        """Whether ignore files are honored while scanning."""
+        ...
+
+    @property
+    def sort_by(self) -> SortOrder:
+        """Ordering applied to scan results."""
+        ...
+
+    @property
+    def strict(self) -> bool:
+        """Whether walk errors fail the scan."""
+        ...
+
+    @property
+    def follow_symlinks(self) -> bool:
+        """Whether symbolic links are followed."""
+        ...
+
+    @property
+    def include_hidden(self) -> bool:
+        """Whether dotfiles and dot-directories are scanned."""
+        ...
+
+    @property
+    def max_depth(self) -> int | None:
+        """Maximum scan depth, or None when unlimited."""
+        ...
+
+    @property
+    def threads(self) -> int | None:
+        """Worker thread count, or None for the default."""
+        ...
+
+    @property
+    def scan_errors(self) -> list[ScanError]:
+        """Paths skipped during the last scan (permission denied, broken symlinks, I/O errors)."""
+        ...
+
+    @property
+    def include(self) -> list[str]:
+        """Include globs applied while scanning."""
+        ...
+
+    @property
+    def exclude(self) -> list[str]:
+        """Exclude globs applied while scanning."""
+        ...
+
+class CustomStage:
+    """An ETL stage outside the built-in EtlStage table, e.g. Validate or Publish."""
+
+    name: str
+    ordinal: int
+    """Position in the pipeline on the `EtlStage.ordinal` scale (25: after transform)."""
+    aliases: list[str]
+    """Folder names standing for the stage besides its name."""
+
+    def __init__(self, name: str, ordinal: int, aliases: list[str] | None = None) -> None: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+
+class CustomSystem:
+    """A target system outside the built-in SystemType table, e.g. Trino or Kafka."""
+
+    name: str
+    aliases: list[str]
+    """Folder names standing for the system besides its name."""
+    attributes: dict[str, str]
+    """Free-form attributes such as a dialect or connection hint."""
+
+    def __init__(
+        self,
+        name: str,
+        aliases: list[str] | None = None,
+        attributes: dict[str, str] | None = None,
+    ) -> None: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
+    @override
+    def __str__(self) -> str: ...
+    @override
+    def __repr__(self) -> str: ...
+
+class AliasRegistry:
+    """Stage and system aliases added at runtime on top of the built-in tables."""
+
+    stages: dict[str, Stage]
+    """User-defined stage aliases (lowercase), custom stages' own aliases included."""
+    systems: dict[str, System]
+    """User-defined system aliases (lowercase), custom systems' own aliases included."""
+    custom_stages: list[CustomStage]
+    """Registered custom stages, in pipeline order."""
+    custom_systems: list[CustomSystem]
+    """Registered custom systems."""
+
+    def __init__(
+        self,
+        stages: dict[str, Stage | str] | None = None,
+        systems: dict[str, System | str] | None = None,
+        custom_stages: "list[CustomStage] | dict[str, dict[str, Any]] | None" = None,
+        custom_systems: "list[CustomSystem] | dict[str, dict[str, Any]] | None" = None,
+    ) -> None:
+        """
+        Map aliases to enum members, custom stages and systems or their names.
+        Custom stages and systems are registered under their name and aliases
+        before `stages` and `systems` are read.
+
+        Raises:
+            ValueError: If a target is not a known stage or system
+        """
+        ...
+    @staticmethod
+    def from_file(path: str | Path) -> "AliasRegistry":
+        """
+        Load `{"stages": {alias: name}, "systems": {alias: name}, "custom_stages":
+        {name: {"ordinal": 25, "aliases": [...]}}, "custom_systems": {name:
+        {"aliases": [...], "attributes": {...}}}}` from a JSON file.
+        """
+        ...
+    def add_stage(self, alias: str, stage: "Stage | str") -> None: ...
+    def add_system(self, alias: str, system: "System | str") -> None: ...
+    def register_stage(self, stage: CustomStage) -> None: ...
+    def register_system(self, system: CustomSystem) -> None: ...
+    def stage(self, alias: str) -> "Stage | None": ...
+    def system(self, alias: str) -> "System | None": ...
+    @override
+    def __repr__(self) -> str: ...
+
+class SegmentMatch:
+    """How the classifier read one folder of a task path."""
+
+    folder: str
+    role: SegmentRole
+    """What the folder was used for."""
+    stage: "Stage | None"
+    """Stage the folder is an alias of, whatever it was used for."""
+    system: "System | None"
+    """System the folder is an alias of, whatever it was used for."""
+    rule: str
+    """Why the folder got its role, e.g. "first stage alias (extract)"."""
+    ambiguous: bool
+    """The folder is an alias of both a stage and a system, or was not used as one."""
+
+    @override
+    def __repr__(self) -> str: ...
+
+class Explanation:
+    """Result of `FileClassifier.explain`."""
+
+    path: str
+    segments: list[SegmentMatch]
+    """Folders between the base path and the file, outermost first."""
+    subtask: Subtask | None
+    error: SubtaskError | None
+    warnings: list[SubtaskError]
+    """Ambiguous folders, reported whether or not the classifier is strict."""
+
+    @override
+    def __repr__(self) -> str: ...
+    @override
+    def __str__(self) -> str:
+        """One line per folder and the outcome, for printing."""
+        ...
+
 class FileClassifier:
     """Classifier for converting file paths into Subtask objects based on folder structure."""
 
     base_path: str
+    layout: str | None
+    max_entity_depth: int | None
+    aliases: AliasRegistry
+    strict: bool
+    read_headers: bool
 
-    def __init__(self, base_path: str | Path) -> None:
+    def __init__(
+        self,
+        base_path: str | Path,
+        layout: str | None = None,
+        max_entity_depth: int | None = 1,
+        aliases: "Aliases | None" = None,
+        strict: bool = False,
+        read_headers: bool = False,
+    ) -> None:
         """
         Initialize FileClassifier with a base path.
 
         Args:
             base_path: Base directory path (string path or pathlib.Path)
+            layout: Folder layout template such as "{system}/{entity}/{stage}";
+                `{name?}` marks an optional segment. Without one, stage and system
+                folders are recognised by their aliases wherever they are.
+            max_entity_depth: How many folders an entity may span (None: any number)
+            aliases: Extra stage and system aliases: an AliasRegistry, a dict shaped
+                like {"stages": {...}, "systems": {...}} or the path of a JSON file
+            strict: Fail on ambiguous folders (an entity named like an alias, two
+                folders naming a system) with `ErrorKind.Ambiguous`; with a header, a
+                stage or system that contradicts the folders fails instead of overriding them
+            read_headers: Read `@key: value` header comments of loaded task files
+
+        Raises:
+            ValueError: If the layout template or max_entity_depth is invalid
         """
         ...
 
-    def classify(self, file_path: str | Path) -> Subtask:
+    def classify(self, file_path: str | Path, content: str | None = None) -> Subtask:
         """
         Classify a file path into a Subtask based on its location relative to base_path.
 
         Args:
             file_path: Path to the file to classify (string path or pathlib.Path)
+            content: File content whose header (`-- @stage: load`, YAML front matter)
+                overrides the stage, system and entity and fills `metadata`
 
         Returns:
             A Subtask object with extracted metadata
@@ -286,6 +873,14 @@ class FileClassifier:
             ValueError: If the folder structure is invalid or task type cannot be determined
         """
         ...
+    def explain(self, file_path: str | Path) -> Explanation:
+        """
+        Classify a file path and report what each folder matched and which rule
+        decided it; classification errors are returned in `error`, not raised.
+        """
+        ...
+    def stage_for(self, alias: str) -> "Stage | None": ...
+    def system_type_for(self, alias: str) -> "System | None": ...
 
     @override
     def __repr__(self) -> str: ...
@@ -1,6 +1,14 @@
 import pytest
 
-from subtask_manager import EtlStage, SystemType, TaskType
+from subtask_manager import (
+    ChangeKind,
+    ErrorKind,
+    ErrorPolicy,
+    EtlStage,
+    SortOrder,
+    SystemType,
+    TaskType,
+)
 
 
 # ------------------------
@@ -58,4 +66,42 @@ def test_etlstage_from_alias_valid():
 
 def test_etlstage_from_folder_name_invalid():
     with pytest.raises(ValueError, match="Unknown ETL stage alias: invalid_stage"):
-        _ = EtlStage.from_alias("invalid_stage")
\ No newline at end of file
+        _ = EtlStage.from_alias("invalid_stage")
+
+
+# ------------------------
+# SortOrder tests
+# ------------------------
+def test_sortorder_from_alias():
+    assert SortOrder.from_alias("path") == SortOrder.Path
+    assert SortOrder.from_alias("STAGE") == SortOrder.Stage
+    assert SortOrder.Stage.id == 1
+    with pytest.raises(ValueError, match="Unknown sort order alias: size"):
+        _ = SortOrder.from_alias("size")
+
+
+# ------------------------
+# ErrorPolicy / ErrorKind tests
+# ------------------------
+def test_errorpolicy_from_alias():
+    assert ErrorPolicy.from_alias("raise") == ErrorPolicy.Raise
+    assert ErrorPolicy.from_alias("skip") == ErrorPolicy.Skip
+    assert ErrorPolicy.from_alias("Collect") == ErrorPolicy.Collect
+    with pytest.raises(ValueError, match="Unknown error policy alias: retry"):
+        _ = ErrorPolicy.from_alias("retry")
+
+
+def test_errorkind_names():
+    assert ErrorKind.FolderStructure.name == "folder_structure"
+    assert str(ErrorKind.Encoding) == "encoding"
+    assert ErrorKind.TooLarge.name == "too_large"
+    assert ErrorKind.Binary.id == 6
+
+
+def test_changekind_names():
+    assert [k.name for k in (ChangeKind.Added, ChangeKind.Modified, ChangeKind.Removed)] == [
+        "added",
+        "modified",
+        "removed",
+    ]
+    assert repr(ChangeKind.Removed) == "ChangeKind.REMOVED"
@@ -2,7 +2,18 @@ from pathlib import Path
 
 import pytest
 
-from subtask_manager import EtlStage, FileClassifier, Subtask, SystemType, TaskType
+from subtask_manager import (
+    AliasRegistry,
+    CustomStage,
+    CustomSystem,
+    ErrorKind,
+    EtlStage,
+    FileClassifier,
+    SegmentRole,
+    Subtask,
+    SystemType,
+    TaskType,
+)
 
 
 def test_classify_simple_sql_file(tmp_path: Path):
@@ -45,3 +56,321 @@ def test_classify_invalid_extension(tmp_path: Path):
 
     with pytest.raises(ValueError, match="Unknown task type"):
         _ =  classifier.classify(file_path)
+
+
+def test_classify_with_layout(tmp_path: Path):
+    classifier = FileClassifier(tmp_path, layout="{system}/{entity}/{stage}")
+    assert classifier.layout == "{system}/{entity}/{stage}"
+    assert "layout=" in repr(classifier)
+
+    subtask = classifier.classify(tmp_path / "pg" / "orders" / "02_transform" / "a.sql")
+    assert subtask.system_type == SystemType.PostgreSQL
+    assert subtask.entity == "orders"
+    assert subtask.stage == EtlStage.Transform
+
+    # positions matter: the heuristic would accept this order, the layout does not
+    with pytest.raises(ValueError, match="does not match layout"):
+        _ = classifier.classify(tmp_path / "orders" / "02_transform" / "pg" / "a.sql")
+
+    # files directly under the base are still common tasks
+    assert classifier.classify(tmp_path / "shared.yaml").is_common
+
+
+def test_classify_with_optional_and_named_segments(tmp_path: Path):
+    classifier = FileClassifier(tmp_path, layout="{domain}/{entity}/{stage}/{system?}")
+
+    deep = classifier.classify(tmp_path / "sales" / "orders" / "load" / "duck" / "a.sql")
+    assert deep.entity == "orders"
+    assert deep.stage == EtlStage.Load
+    assert deep.system_type == SystemType.Duckdb
+
+    short = classifier.classify(tmp_path / "sales" / "orders" / "load" / "a.sql")
+    assert short.stage == EtlStage.Load
+    assert short.system_type is None
+
+    # without a layout more than 3 folders are refused
+    with pytest.raises(ValueError, match="Incorrect folder structure"):
+        _ = FileClassifier(tmp_path).classify(
+            tmp_path / "sales" / "orders" / "load" / "duck" / "a.sql"
+        )
+
+
+def test_classify_invalid_layout(tmp_path: Path):
+    for layout in ["", "{stage}/{stage}", "{bad-name}", "a//b"]:
+        with pytest.raises(ValueError, match="Invalid layout"):
+            _ = FileClassifier(tmp_path, layout=layout)
+
+
+def test_classify_entity_path(tmp_path: Path):
+    deep = tmp_path / "finance" / "billing" / "invoices" / "01_extract" / "pg" / "a.sql"
+
+    with pytest.raises(ValueError, match="Incorrect folder structure"):
+        _ = FileClassifier(tmp_path).classify(deep)
+
+    classifier = FileClassifier(tmp_path, max_entity_depth=3)
+    assert classifier.max_entity_depth == 3
+    subtask = classifier.classify(deep)
+    assert subtask.entity_path == ["finance", "billing", "invoices"]
+    assert subtask.entity == "finance/billing/invoices"
+    assert subtask.stage == EtlStage.Extract
+    assert subtask.system_type == SystemType.PostgreSQL
+
+    unlimited = FileClassifier(tmp_path, max_entity_depth=None)
+    nested = tmp_path / "eu" / "de" / "berlin" / "stores" / "x.sql"
+    assert unlimited.classify(nested).entity_path == ["eu", "de", "berlin", "stores"]
+    assert FileClassifier(tmp_path).classify(tmp_path / "orders" / "x.sql").entity_path == [
+        "orders"
+    ]
+
+    with_layout = FileClassifier(
+        tmp_path, layout="{entity}/{stage}/{system}", max_entity_depth=None
+    )
+    assert with_layout.classify(deep).entity_path == ["finance", "billing", "invoices"]
+
+    with pytest.raises(ValueError, match="at least 1"):
+        _ = FileClassifier(tmp_path, max_entity_depth=0)
+
+
+def test_classify_with_aliases(tmp_path: Path):
+    path = tmp_path / "orders" / "10_stg" / "gp" / "a.sql"
+    with pytest.raises(ValueError, match="Incorrect folder structure"):
+        _ = FileClassifier(tmp_path).classify(path)
+
+    aliases = AliasRegistry(
+        stages={"10_stg": EtlStage.Extract, "20_dm": "load"},
+        systems={"gp": SystemType.PostgreSQL, "trino": "other"},
+    )
+    classifier = FileClassifier(tmp_path, aliases=aliases)
+    subtask = classifier.classify(path)
+    assert subtask.stage == EtlStage.Extract
+    assert subtask.system_type == SystemType.PostgreSQL
+    assert subtask.entity == "orders"
+    # built-in aliases keep working
+    assert classifier.classify(tmp_path / "orders" / "load" / "a.sql").stage == EtlStage.Load
+    assert classifier.stage_for("20_DM") == EtlStage.Load
+    assert classifier.system_type_for("trino") == SystemType.Other
+    assert classifier.stage_for("nope") is None
+
+    # a plain dict works too, and so does a layout
+    positional = FileClassifier(
+        tmp_path,
+        layout="{system}/{entity}/{stage}",
+        aliases={"stages": {"20_dm": "load"}, "systems": {"trino": "other"}},
+    )
+    subtask = positional.classify(tmp_path / "trino" / "sales" / "20_dm" / "a.sql")
+    assert subtask.stage == EtlStage.Load
+    assert subtask.system_type == SystemType.Other
+    assert positional.aliases.stages == {"20_dm": EtlStage.Load}
+
+
+def test_alias_registry(tmp_path: Path):
+    registry = AliasRegistry()
+    registry.add_stage("10_STG", EtlStage.Extract)
+    registry.add_system("gp", SystemType.PostgreSQL)
+    assert registry.stages == {"10_stg": EtlStage.Extract}
+    assert registry.systems == {"gp": SystemType.PostgreSQL}
+
+    assert EtlStage.from_alias("10_stg", registry) == EtlStage.Extract
+    assert EtlStage.from_alias("extract", registry) == EtlStage.Extract
+    assert SystemType.from_alias("GP", aliases=registry) == SystemType.PostgreSQL
+    with pytest.raises(ValueError):
+        _ = EtlStage.from_alias("10_stg")
+
+    config = tmp_path / "aliases.json"
+    _ = config.write_text('{"stages": {"20_dm": "load"}, "systems": {"gp": "pg"}}')
+    loaded = AliasRegistry.from_file(config)
+    assert loaded.stages == {"20_dm": EtlStage.Load}
+    assert FileClassifier(tmp_path, aliases=config).stage_for("20_dm") == EtlStage.Load
+
+    with pytest.raises(ValueError, match="Unknown ETL stage alias"):
+        _ = AliasRegistry(stages={"x": "nope"})
+    with pytest.raises(ValueError, match="Unknown alias table"):
+        _ = FileClassifier(tmp_path, aliases={"tasks": {"x": "sql"}})
+    with pytest.raises(ValueError, match="Cannot read"):
+        _ = AliasRegistry.from_file(tmp_path / "missing.json")
+
+
+def test_custom_systems(tmp_path: Path):
+    trino = CustomSystem("Trino", aliases=["tr"], attributes={"catalog": "hive"})
+    assert (trino.name, trino.aliases, trino.attributes) == ("Trino", ["tr"], {"catalog": "hive"})
+    assert str(trino) == "Trino"
+    assert trino == CustomSystem("Trino", ["tr"], {"catalog": "hive"})
+    with pytest.raises(ValueError, match="needs a name"):
+        _ = CustomSystem(" ")
+
+    registry = AliasRegistry(custom_systems=[trino], systems={"presto": "trino"})
+    registry.register_system(CustomSystem("S3", aliases=["s3_raw"]))
+    registry.add_system("pg_stub", SystemType.PostgreSQL)
+    assert [c.name for c in registry.custom_systems] == ["S3", "Trino"]
+    assert registry.systems["presto"] == trino
+    assert registry.system("TR") == trino
+    assert registry.system("pg_stub") == SystemType.PostgreSQL
+    assert registry.system("nope") is None
+    assert SystemType.from_alias("s3_raw", registry) == CustomSystem("S3", ["s3_raw"])
+
+    classifier = FileClassifier(tmp_path, aliases=registry)
+    subtask = classifier.classify(tmp_path / "orders" / "extract" / "tr" / "a.sql")
+    assert subtask.system_type == trino
+    assert subtask.entity == "orders"
+    # built-in systems keep working next to custom ones
+    builtin = classifier.classify(tmp_path / "orders" / "load" / "duck" / "a.sql")
+    assert builtin.system_type == SystemType.Duckdb
+    assert classifier.system_type_for("presto") == trino
+
+    positional = FileClassifier(tmp_path, layout="{system}/{entity}", aliases=registry)
+    assert positional.classify(tmp_path / "s3_raw" / "events" / "a.py").system_type.name == "S3"
+
+    assert Subtask("a.sql", system_type=trino).system_type == trino
+    assert Subtask("a.sql", system_type="pg").system_type == SystemType.PostgreSQL
+
+    config = tmp_path / "aliases.json"
+    _ = config.write_text(
+        '{"custom_systems": {"kafka": {"aliases": ["topics"]}}, "systems": {"kf": "kafka"}}'
+    )
+    loaded = AliasRegistry.from_file(config)
+    assert loaded.system("topics") == CustomSystem("kafka", ["topics"])
+    assert loaded.system("kf") == CustomSystem("kafka", ["topics"])
+    with pytest.raises(ValueError, match="Unknown system type alias"):
+        _ = AliasRegistry(systems={"x": "snowflake"})
+
+
+def test_custom_stages(tmp_path: Path):
+    validate = CustomStage("Validate", 25, aliases=["qa"])
+    assert (validate.name, validate.ordinal, validate.aliases) == ("Validate", 25, ["qa"])
+    assert EtlStage.Transform.ordinal < validate.ordinal < EtlStage.Load.ordinal
+    with pytest.raises(ValueError, match="needs a name"):
+        _ = CustomStage("", 1)
+
+    registry = AliasRegistry(custom_stages=[validate], stages={"check": "validate"})
+    registry.register_stage(CustomStage("Archive", 70))
+    registry.add_stage("10_stg", "extract")
+    assert [s.name for s in registry.custom_stages] == ["Validate", "Archive"]
+    assert registry.stages["check"] == validate
+    assert registry.stage("QA") == validate
+    assert registry.stage("10_stg") == EtlStage.Extract
+    assert EtlStage.from_alias("archive", registry) == CustomStage("Archive", 70)
+
+    classifier = FileClassifier(tmp_path, aliases=registry)
+    subtask = classifier.classify(tmp_path / "orders" / "qa" / "pg" / "a.sql")
+    assert subtask.stage == validate
+    assert subtask.entity == "orders"
+    assert classifier.stage_for("check") == validate
+
+    positional = FileClassifier(tmp_path, layout="{entity}/{stage}", aliases=registry)
+    assert positional.classify(tmp_path / "orders" / "archive" / "a.sql").stage.ordinal == 70
+
+    assert Subtask("a.sql", stage=validate).stage == validate
+    assert Subtask("a.sql", stage="t").stage == EtlStage.Transform
+
+    with pytest.raises(ValueError, match="needs an ordinal"):
+        _ = AliasRegistry(custom_stages={"publish": {"aliases": ["pub"]}})
+
+
+def test_explain(tmp_path: Path):
+    classifier = FileClassifier(tmp_path)
+    explanation = classifier.explain(tmp_path / "orders" / "01_extract" / "pg" / "a.sql")
+    assert [(s.folder, s.role) for s in explanation.segments] == [
+        ("orders", SegmentRole.Entity),
+        ("01_extract", SegmentRole.Stage),
+        ("pg", SegmentRole.System),
+    ]
+    assert explanation.segments[1].stage == EtlStage.Extract
+    assert explanation.segments[2].system == SystemType.PostgreSQL
+    assert "first stage alias" in explanation.segments[1].rule
+    assert not any(s.ambiguous for s in explanation.segments)
+    assert explanation.subtask is not None and explanation.subtask.entity == "orders"
+    assert explanation.error is None
+    assert "orders" in str(explanation)
+
+    # a second system folder replaces the first; an entity named like a stage is ambiguous
+    explanation = classifier.explain(tmp_path / "pg" / "load" / "duck" / "a.sql")
+    assert [(s.role, s.ambiguous) for s in explanation.segments] == [
+        (SegmentRole.Ignored, True),
+        (SegmentRole.Stage, False),
+        (SegmentRole.System, False),
+    ]
+    assert explanation.subtask.system_type == SystemType.Duckdb
+
+    # failures are reported instead of raised
+    failed = classifier.explain(tmp_path / "a" / "b" / "c" / "d" / "x.sql")
+    assert failed.subtask is None
+    assert failed.error.kind == ErrorKind.FolderStructure
+    assert all(s.role == SegmentRole.Unmatched for s in failed.segments)
+    assert failed.segments[0].rule == "more than 3 folders"
+
+    positional = FileClassifier(tmp_path, layout="{domain}/{entity}/{stage}")
+    explanation = positional.explain(tmp_path / "sales" / "load" / "transform" / "a.sql")
+    assert [s.role for s in explanation.segments] == [
+        SegmentRole.Ignored,
+        SegmentRole.Entity,
+        SegmentRole.Stage,
+    ]
+    assert explanation.segments[0].rule == "layout placeholder {domain}"
+    assert explanation.segments[1].ambiguous
+    mismatch = positional.explain(tmp_path / "sales" / "a.sql")
+    assert "does not match layout" in mismatch.error.message
+
+
+def test_strict_classification(tmp_path: Path):
+    two_systems = tmp_path / "orders" / "pg" / "duck" / "a.sql"
+    entity_alias = tmp_path / "load" / "transform" / "a.sql"
+
+    lenient = FileClassifier(tmp_path)
+    assert lenient.strict is False
+    assert lenient.classify(two_systems).system_type == SystemType.Duckdb
+    warnings = lenient.explain(two_systems).warnings
+    assert [w.kind for w in warnings] == [ErrorKind.Ambiguous]
+    assert warnings[0].message == "Folders 'pg' and 'duck' both name a system; 'duck' is used"
+    assert lenient.explain(tmp_path / "orders" / "load" / "pg" / "a.sql").warnings == []
+
+    strict = FileClassifier(tmp_path, strict=True)
+    with pytest.raises(ValueError, match="both name a system"):
+        _ = strict.classify(two_systems)
+    # a later stage alias becomes the entity in lenient mode
+    assert lenient.classify(entity_alias).entity == "transform"
+    with pytest.raises(ValueError, match="'transform' is a stage alias \\(transform\\) but is read as entity"):
+        _ = strict.classify(entity_alias)
+    failed = strict.explain(entity_alias)
+    assert failed.error.kind == ErrorKind.Ambiguous
+    assert len(failed.warnings) == 1
+
+    # a layout decides positions, but an alias in an entity slot is still reported
+    positional = FileClassifier(tmp_path, layout="{entity}/{stage}", strict=True)
+    with pytest.raises(ValueError, match="stage alias \\(load\\) but is read as entity"):
+        _ = positional.classify(entity_alias)
+    assert strict.classify(tmp_path / "orders" / "load" / "pg" / "a.sql").entity == "orders"
+
+
+def test_classify_with_header(tmp_path: Path):
+    classifier = FileClassifier(tmp_path)
+    path = tmp_path / "orders" / "01_extract" / "pg" / "a.sql"
+    content = "-- Orders\n-- @stage: transform\n-- @depends_on: b.sql\n-- @tags: daily\nSELECT 1;\n"
+
+    plain = classifier.classify(path)
+    assert plain.stage == EtlStage.Extract
+    assert plain.metadata == {}
+
+    subtask = classifier.classify(path, content)
+    assert subtask.stage == EtlStage.Transform
+    assert subtask.system_type == SystemType.PostgreSQL
+    assert subtask.metadata == {"depends_on": "b.sql", "tags": "daily"}
+
+    # headers fill what the folders do not say, entities included
+    common = classifier.classify(tmp_path / "job.py", "# @entity: finance/billing\n# @system: duck\n")
+    assert common.entity_path == ["finance", "billing"]
+    assert common.system_type == SystemType.Duckdb
+
+    with pytest.raises(ValueError, match="Unknown stage 'nope' in header"):
+        _ = classifier.classify(path, "-- @stage: nope\n")
+
+    # strict classifiers validate instead of overriding
+    strict = FileClassifier(tmp_path, strict=True)
+    with pytest.raises(ValueError, match="Header @stage: transform contradicts the folders \\(extract\\)"):
+        _ = strict.classify(path, content)
+    assert strict.classify(path, "-- @stage: e\n-- @system: postgres\n").stage == EtlStage.Extract
+
+    yaml = "---\nstage: load\nowner: data-team\n---\njob: x\n"
+    subtask = classifier.classify(tmp_path / "orders" / "job.yaml", yaml)
+    assert subtask.stage == EtlStage.Load
+    assert subtask.metadata == {"owner": "data-team"}
+
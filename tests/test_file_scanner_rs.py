@@ -1,10 +1,11 @@
 # tests/test_file_scanner.py
 import os
+import zipfile
 from pathlib import Path
 
 import pytest
 
-from subtask_manager import FileScanner
+from subtask_manager import FileScanner, SortOrder
 
 
 @pytest.mark.parametrize("extensions,expected", [
@@ -27,3 +28,278 @@ def test_scan_files(tmp_path: Path, extensions:list[str], expected:list[str]):
     # Assert
     found_files = {os.path.basename(p) for p in result}
     assert found_files == set(expected)
+
+
+def test_scan_files_respects_ignore_files(tmp_path: Path):
+    _ = (tmp_path / ".gitignore").write_text("scratch_*.sql\nbuild/\n")
+    _ = (tmp_path / ".subtaskignore").write_text("vendor/\n!vendor/keep.sql\n")
+    for rel in [
+        "task.sql",
+        "scratch_1.sql",
+        "build/out.sql",
+        "vendor/lib.sql",
+        "nested/inner.sql",
+        "nested/skip.sql",
+    ]:
+        path = tmp_path / rel
+        path.parent.mkdir(parents=True, exist_ok=True)
+        _ = path.write_text("select 1;")
+    _ = (tmp_path / "nested" / ".ignore").write_text("skip.sql\n")
+
+    result = FileScanner([".sql"]).scan_files(tmp_path)
+
+    found = {Path(p).relative_to(tmp_path).as_posix() for p in result}
+    assert found == {"task.sql", "nested/inner.sql"}
+
+
+def test_scan_files_negation_in_subtaskignore(tmp_path: Path):
+    _ = (tmp_path / ".subtaskignore").write_text("*.sql\n!keep.sql\n")
+    _ = (tmp_path / "drop.sql").write_text("select 1;")
+    _ = (tmp_path / "keep.sql").write_text("select 1;")
+
+    result = FileScanner([".sql"]).scan_files(tmp_path)
+
+    assert {os.path.basename(p) for p in result} == {"keep.sql"}
+
+
+def test_scan_files_without_ignore_files(tmp_path: Path):
+    _ = (tmp_path / ".gitignore").write_text("*.sql\n")
+    _ = (tmp_path / "task.sql").write_text("select 1;")
+
+    scanner = FileScanner([".sql"], respect_ignore_files=False)
+
+    assert scanner.respect_ignore_files is False
+    assert {os.path.basename(p) for p in scanner.scan_files(tmp_path)} == {"task.sql"}
+
+
+def _write_tree(base: Path, rel_paths: list[str]) -> None:
+    for rel in rel_paths:
+        path = base / rel
+        path.parent.mkdir(parents=True, exist_ok=True)
+        _ = path.write_text("select 1;")
+
+
+def test_scan_files_include_exclude(tmp_path: Path):
+    _write_tree(
+        tmp_path,
+        [
+            "customers/01_extract/pg/a.sql",
+            "customers/01_extract/deprecated/old.sql",
+            "customers/02_transform/pg/b.sql",
+            "shared.sql",
+        ],
+    )
+
+    scanner = FileScanner(
+        [".sql"], include=["**/01_extract/**"], exclude=["**/deprecated/**"]
+    )
+    result = scanner.scan_files(tmp_path)
+
+    found = {Path(p).relative_to(tmp_path).as_posix() for p in result}
+    assert found == {"customers/01_extract/pg/a.sql"}
+    assert scanner.include == ["**/01_extract/**"]
+    assert scanner.exclude == ["**/deprecated/**"]
+
+
+def test_scan_files_negated_include_pattern(tmp_path: Path):
+    _write_tree(tmp_path, ["a/keep.sql", "a/deprecated/drop.sql"])
+
+    scanner = FileScanner([".sql"], include=["**/*.sql", "!**/deprecated/**"])
+    result = scanner.scan_files(tmp_path)
+
+    assert {os.path.basename(p) for p in result} == {"keep.sql"}
+    assert scanner.include == ["**/*.sql"]
+    assert scanner.exclude == ["**/deprecated/**"]
+
+
+def test_scan_files_file_exclude_keeps_directory(tmp_path: Path):
+    _write_tree(tmp_path, ["views.sql/a.py", "views.sql/b.sql", "c.sql"])
+
+    scanner = FileScanner([".sql", ".py"], exclude=["**/*.sql"])
+    result = scanner.scan_files(tmp_path)
+
+    found = {Path(p).relative_to(tmp_path).as_posix() for p in result}
+    assert found == {"views.sql/a.py"}
+
+
+def test_scan_files_invalid_glob(tmp_path: Path):
+    with pytest.raises(ValueError, match="Invalid glob pattern"):
+        _ = FileScanner([".sql"], include=["a/[b"])
+
+
+def test_scan_files_sorted_by_path(tmp_path: Path):
+    _write_tree(tmp_path, ["b.sql", "a/z.sql", "a-b.sql", "a/b/c.sql"])
+
+    result = FileScanner([".sql"]).scan_files(tmp_path)
+
+    assert [Path(p).relative_to(tmp_path).as_posix() for p in result] == [
+        "a/b/c.sql",
+        "a/z.sql",
+        "a-b.sql",
+        "b.sql",
+    ]
+
+
+def test_scan_files_sorted_by_stage(tmp_path: Path):
+    _write_tree(
+        tmp_path,
+        [
+            "shared.sql",
+            "orders/03_load/pg/load.sql",
+            "customers/02_transform/duck/10_merge.sql",
+            "customers/02_transform/duck/2_clean.sql",
+            "orders/01_extract/pg/extract.sql",
+        ],
+    )
+
+    scanner = FileScanner([".sql"], sort_by=SortOrder.Stage)
+    result = scanner.scan_files(tmp_path)
+
+    assert scanner.sort_by == SortOrder.Stage
+    assert [Path(p).relative_to(tmp_path).as_posix() for p in result] == [
+        "orders/01_extract/pg/extract.sql",
+        "customers/02_transform/duck/2_clean.sql",
+        "customers/02_transform/duck/10_merge.sql",
+        "orders/03_load/pg/load.sql",
+        "shared.sql",
+    ]
+
+
+def test_scan_files_reports_broken_symlink(tmp_path: Path):
+    _write_tree(tmp_path, ["ok.sql"])
+    (tmp_path / "dangling.sql").symlink_to(tmp_path / "missing.sql")
+
+    scanner = FileScanner([".sql"])
+    result = scanner.scan_files(tmp_path)
+
+    assert {os.path.basename(p) for p in result} == {"ok.sql"}
+    assert len(scanner.scan_errors) == 1
+    error = scanner.scan_errors[0]
+    assert os.path.basename(error.path) == "dangling.sql"
+    assert "broken symbolic link" in error.reason
+
+
+def test_scan_files_reports_missing_base_dir(tmp_path: Path):
+    missing = tmp_path / "missing"
+
+    scanner = FileScanner([".sql"])
+
+    assert scanner.scan_files(missing) == []
+    assert [e.path for e in scanner.scan_errors] == [str(missing)]
+
+
+def test_scan_files_strict_raises_on_errors(tmp_path: Path):
+    (tmp_path / "dangling.sql").symlink_to(tmp_path / "missing.sql")
+
+    scanner = FileScanner([".sql"], strict=True)
+
+    assert scanner.strict is True
+    with pytest.raises(RuntimeError, match="dangling.sql"):
+        _ = scanner.scan_files(tmp_path)
+    assert len(scanner.scan_errors) == 1
+
+
+def test_scan_files_symlink_policy(tmp_path: Path):
+    shared = tmp_path / "shared"
+    _write_tree(shared, ["common.sql"])
+    project = tmp_path / "project"
+    _write_tree(project, ["own.sql"])
+    (project / "linked").symlink_to(shared, target_is_directory=True)
+
+    not_following = FileScanner([".sql"])
+    assert {os.path.basename(p) for p in not_following.scan_files(project)} == {
+        "own.sql"
+    }
+
+    following = FileScanner([".sql"], follow_symlinks=True)
+    assert following.follow_symlinks is True
+    assert {os.path.basename(p) for p in following.scan_files(project)} == {
+        "own.sql",
+        "common.sql",
+    }
+
+
+def test_scan_files_reports_symlink_loop(tmp_path: Path):
+    _write_tree(tmp_path, ["a/task.sql"])
+    (tmp_path / "a" / "back").symlink_to(tmp_path, target_is_directory=True)
+
+    scanner = FileScanner([".sql"], follow_symlinks=True)
+    result = scanner.scan_files(tmp_path)
+
+    assert {os.path.basename(p) for p in result} == {"task.sql"}
+    assert len(scanner.scan_errors) == 1
+    assert "loop" in scanner.scan_errors[0].reason.lower()
+
+
+def test_scan_files_hidden_policy(tmp_path: Path):
+    _write_tree(tmp_path, ["visible.sql", ".hidden.sql", ".drafts/draft.sql"])
+
+    assert len(FileScanner([".sql"]).scan_files(tmp_path)) == 3
+
+    scanner = FileScanner([".sql"], include_hidden=False)
+    assert scanner.include_hidden is False
+    assert {os.path.basename(p) for p in scanner.scan_files(tmp_path)} == {
+        "visible.sql"
+    }
+
+
+def test_scan_files_max_depth(tmp_path: Path):
+    _write_tree(tmp_path, ["top.sql", "a/one.sql", "a/b/two.sql"])
+
+    scanner = FileScanner([".sql"], max_depth=2)
+    assert scanner.max_depth == 2
+    assert {os.path.basename(p) for p in scanner.scan_files(tmp_path)} == {
+        "top.sql",
+        "one.sql",
+    }
+    assert FileScanner([".sql"]).max_depth is None
+
+
+def test_scan_files_parallel_is_deterministic(tmp_path: Path):
+    _write_tree(
+        tmp_path,
+        [f"entity_{i % 7}/0{i % 3}_stage/task_{i}.sql" for i in range(200)],
+    )
+
+    results = [
+        FileScanner([".sql"], threads=threads).scan_files(tmp_path)
+        for threads in (None, 1, 2, 8)
+    ]
+
+    assert len(results[0]) == 200
+    assert all(r == results[0] for r in results)
+    assert FileScanner([".sql"], threads=4).threads == 4
+
+
+def test_scan_zip_archive(tmp_path: Path):
+    archive = tmp_path / "tasks.zip"
+    with zipfile.ZipFile(archive, "w") as zf:
+        zf.writestr("etl/.subtaskignore", "scratch/\n")
+        zf.writestr("etl/b/task.sql", "SELECT 2;")
+        zf.writestr("etl/a.sql", "SELECT 1;")
+        zf.writestr("etl/scratch/tmp.sql", "SELECT 3;")
+        zf.writestr("etl/.hidden/h.sql", "SELECT 4;")
+        zf.writestr("etl/notes.txt", "notes")
+        zf.writestr("other/x.sql", "SELECT 5;")
+        zf.writestr("../evil.sql", "SELECT 6;")
+
+    scanner = FileScanner(["sql"])
+    assert scanner.scan_files(archive) == [
+        "etl/.hidden/h.sql",
+        "etl/a.sql",
+        "etl/b/task.sql",
+        "other/x.sql",
+    ]
+    assert [e.path for e in scanner.scan_errors] == ["../evil.sql"]
+
+    assert scanner.scan_files(archive, archive_prefix="etl/") == [
+        "etl/.hidden/h.sql",
+        "etl/a.sql",
+        "etl/b/task.sql",
+    ]
+    assert FileScanner(["sql"], include_hidden=False, max_depth=1).scan_files(
+        archive, archive_prefix="etl"
+    ) == ["etl/a.sql"]
+    assert FileScanner(["sql"], respect_ignore_files=False).scan_files(
+        archive, archive_prefix="etl"
+    ) == ["etl/.hidden/h.sql", "etl/a.sql", "etl/b/task.sql", "etl/scratch/tmp.sql"]
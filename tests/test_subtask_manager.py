@@ -1,10 +1,22 @@
+import json
+import os
+import tarfile
+import threading
+import time
+import zipfile
 from pathlib import Path
 
 import pytest
 
 from subtask_manager import (
+    ChangeKind,
+    CustomStage,
+    CustomSystem,
+    ErrorKind,
+    ErrorPolicy,
     EtlStage,
     FileClassifier,
+    SortOrder,
     Subtask,
     SubtaskManager,
     SystemType,
@@ -142,3 +154,870 @@ def test_existing_path():
 
     tasks = sm.get_tasks(entity="customers")
     assert len(tasks) == 3
+
+
+def test_manager_skips_ignored_files(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    scratch = base / "customers" / "01_extract" / "pg" / "scratch.sql"
+    _ = scratch.write_text("SELECT 1;")
+    _ = (base / ".subtaskignore").write_text("scratch.sql\n")
+
+    manager = SubtaskManager(base)
+
+    assert manager.num_files == 3
+    assert all(s.name != "scratch.sql" for s in manager.subtasks)
+
+    assert SubtaskManager(base, respect_ignore_files=False).num_files == 4
+
+
+def test_manager_include_exclude(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+
+    manager = SubtaskManager(base, include=["customers/**"], exclude=["**/02_transform/**"])
+
+    assert [s.name for s in manager.subtasks] == ["get_customers.sql"]
+    assert manager.include == ["customers/**"]
+    assert manager.exclude == ["**/02_transform/**"]
+
+
+def test_manager_ordering_is_stable(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+
+    manager = SubtaskManager(base)
+    assert [s.name for s in manager.subtasks] == [
+        "get_customers.sql",
+        "sales.py",
+        "shared.yaml",
+    ]
+    assert manager.sort_by == SortOrder.Path
+
+    staged = SubtaskManager(base, sort_by=SortOrder.Stage)
+    assert [s.name for s in staged.subtasks] == [
+        "get_customers.sql",
+        "sales.py",
+        "shared.yaml",
+    ]
+    assert [Path(p).name for p in staged.file_paths] == [
+        s.name for s in staged.subtasks
+    ]
+
+
+def test_manager_scan_errors(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    (base / "dangling.sql").symlink_to(base / "missing.sql")
+
+    manager = SubtaskManager(base)
+
+    assert manager.num_files == 3
+    assert [Path(e.path).name for e in manager.scan_errors] == ["dangling.sql"]
+
+    with pytest.raises(RuntimeError, match="Scan failed"):
+        _ = SubtaskManager(base, strict=True)
+
+
+def test_manager_scan_policy(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    _ = (base / ".draft.sql").write_text("SELECT 1;")
+
+    assert SubtaskManager(base).num_files == 4
+
+    manager = SubtaskManager(base, include_hidden=False, max_depth=1)
+    assert [s.name for s in manager.subtasks] == ["shared.yaml"]
+    assert manager.include_hidden is False
+    assert manager.max_depth == 1
+    assert manager.follow_symlinks is False
+
+
+def test_manager_parallel_loading(tmp_path: Path):
+    for i in range(100):
+        task_dir = tmp_path / f"entity_{i % 5}" / "01_extract" / "pg"
+        task_dir.mkdir(parents=True, exist_ok=True)
+        _ = (task_dir / f"task_{i}.sql").write_text(f"SELECT {i};")
+
+    single = SubtaskManager(tmp_path, threads=1)
+    multi = SubtaskManager(tmp_path, threads=8)
+
+    assert multi.threads == 8
+    assert [s.path for s in multi.subtasks] == [s.path for s in single.subtasks]
+    assert [s.path for s in multi.subtasks] == multi.file_paths
+    assert all(s.command == f"SELECT {s.name[5:-4]};" for s in multi.subtasks)
+
+
+def _add_broken_files(base: Path) -> tuple[Path, Path]:
+    deep = base / "customers" / "01_extract" / "pg" / "deep" / "nested"
+    deep.mkdir(parents=True)
+    too_deep = deep / "bad.sql"
+    _ = too_deep.write_text("SELECT 1;")
+    not_utf8 = base / "customers" / "01_extract" / "pg" / "latin.sql"
+    _ = not_utf8.write_bytes("SELECT 'café';".encode("latin-1"))
+    return too_deep, not_utf8
+
+
+def test_manager_on_error_raise(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    too_deep, _ = _add_broken_files(base)
+
+    manager = SubtaskManager(base)
+
+    assert manager.on_error == ErrorPolicy.Raise
+    with pytest.raises(RuntimeError, match="Incorrect folder structure"):
+        _ = manager.subtasks
+    with pytest.raises(RuntimeError, match=str(too_deep.name)):
+        manager.load_all()
+
+
+def test_manager_on_error_skip(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    _ = _add_broken_files(base)
+
+    manager = SubtaskManager(base, on_error=ErrorPolicy.Skip)
+    manager.load_all()
+
+    assert len(manager.subtasks) == 3
+    assert manager.errors == []
+
+
+def test_manager_on_error_collect(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    too_deep, not_utf8 = _add_broken_files(base)
+
+    manager = SubtaskManager(base, on_error=ErrorPolicy.Collect)
+    manager.load_all()
+
+    assert len(manager.subtasks) == 3
+    errors = {Path(e.path): e for e in manager.errors}
+    assert set(errors) == {too_deep, not_utf8}
+    assert errors[too_deep].kind == ErrorKind.FolderStructure
+    assert errors[too_deep].message == "Incorrect folder structure"
+    assert errors[not_utf8].kind == ErrorKind.Encoding
+
+
+def test_manager_lazy_reads_on_first_access(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    _, not_utf8 = _add_broken_files(base)
+    _ = (base / "customers" / "01_extract" / "pg" / "deep" / "nested" / "bad.sql").unlink()
+
+    manager = SubtaskManager(base)
+
+    # classification alone never reads the undecodable file
+    assert manager.lazy
+    assert len(manager.subtasks) == 4
+    task = manager.get_task("get_customers.sql")
+    assert not task.is_loaded
+    assert task.command == "SELECT * FROM customers;"
+    assert task.is_loaded
+    assert not any(s.is_loaded for s in manager.subtasks if s.name != "get_customers.sql")
+
+    broken = next(s for s in manager.subtasks if Path(s.path) == not_utf8)
+    with pytest.raises(RuntimeError, match="latin.sql"):
+        _ = broken.command
+
+
+def test_manager_lazy_release(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    task = manager.get_task("get_customers.sql")
+    assert task.command == "SELECT * FROM customers;"
+
+    path = Path(task.path)
+    _ = path.write_text("SELECT id FROM customers;")
+    assert task.command == "SELECT * FROM customers;"
+
+    manager.release_contents()
+    assert not task.is_loaded
+    assert task.command == "SELECT id FROM customers;"
+
+    task.release()
+    _ = path.write_text("SELECT 1;")
+    assert task.get_command() == "SELECT 1;"
+
+
+def test_manager_eager_loading(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    _, not_utf8 = _add_broken_files(base)
+
+    manager = SubtaskManager(base, lazy=False, on_error=ErrorPolicy.Collect)
+
+    assert not manager.lazy
+    assert len(manager.subtasks) == 3
+    assert all(s.is_loaded for s in manager.subtasks)
+    assert not_utf8 in {Path(e.path) for e in manager.errors}
+
+
+def test_manager_refresh(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    sql = manager.get_task("get_customers.sql")
+    py = manager.get_task("sales.py")
+    assert sql.command == "SELECT * FROM customers;"
+    assert not manager.refresh()
+
+    added = base / "customers" / "03_load" / "pg" / "load.sql"
+    added.parent.mkdir(parents=True)
+    _ = added.write_text("INSERT INTO customers SELECT 1;")
+    _ = Path(sql.path).write_text("SELECT id, name FROM customers;")
+    Path(py.path).unlink()
+
+    changes = manager.refresh()
+
+    assert changes.added == [str(added)]
+    assert changes.removed == [py.path]
+    assert changes.modified == [sql.path]
+    assert manager.file_paths == [s.path for s in manager.subtasks]
+    assert manager.get_task("get_customers.sql").command == "SELECT id, name FROM customers;"
+    assert manager.get_task("load.sql").stage == EtlStage.Load
+    assert not manager.refresh()
+
+
+def test_manager_refresh_ignores_touch(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    sql = manager.get_task("get_customers.sql")
+    assert sql.command == "SELECT * FROM customers;"
+
+    path = Path(sql.path)
+    stat = path.stat()
+    os.utime(path, ns=(stat.st_atime_ns, stat.st_mtime_ns + 5_000_000_000))
+
+    assert not manager.refresh()
+    assert manager.get_task("get_customers.sql").is_loaded
+
+
+def test_manager_refresh_before_load(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    added = base / "customers" / "01_extract" / "pg" / "orders.sql"
+    _ = added.write_text("SELECT 1;")
+
+    changes = manager.refresh()
+
+    assert changes.added == [str(added)]
+    assert changes.removed == []
+    assert changes.modified == []
+    assert str(added) in [s.path for s in manager.subtasks]
+
+
+def test_manager_refresh_collects_errors(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base, on_error=ErrorPolicy.Collect)
+    assert manager.errors == []
+
+    too_deep, _ = _add_broken_files(base)
+    changes = manager.refresh()
+    assert str(too_deep) in changes.added
+    assert [e.path for e in manager.errors] == [str(too_deep)]
+
+    too_deep.unlink()
+    changes = manager.refresh()
+    assert changes.removed == [str(too_deep)]
+    assert manager.errors == []
+
+
+def _write_later(path: Path, text: str, delay: float = 0.3) -> threading.Thread:
+    def write():
+        time.sleep(delay)
+        _ = path.write_text(text)
+
+    thread = threading.Thread(target=write)
+    thread.start()
+    return thread
+
+
+def test_manager_watch(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    added = base / "customers" / "03_load" / "pg" / "load.sql"
+    added.parent.mkdir(parents=True)
+    batches = []
+
+    def on_change(events):
+        batches.append(events)
+        return False
+
+    writer = _write_later(added, "INSERT INTO customers SELECT 1;")
+    manager.watch(on_change, debounce_ms=50, timeout=10)
+    writer.join()
+
+    assert len(batches) == 1
+    [event] = batches[0]
+    assert event.kind == ChangeKind.Added
+    assert event.path == str(added)
+    assert event.subtask.stage == EtlStage.Load
+    assert event.subtask.entity == "customers"
+    assert event.subtask.command == "INSERT INTO customers SELECT 1;"
+    assert str(added) in manager.file_paths
+
+
+def test_manager_watch_modified_and_removed(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    sql = manager.get_task("get_customers.sql")
+    py = manager.get_task("sales.py")
+    kinds = {}
+
+    def on_change(events):
+        for event in events:
+            kinds[event.path] = event.kind
+        return len(kinds) < 2
+
+    def edit():
+        time.sleep(0.3)
+        _ = Path(sql.path).write_text("SELECT id FROM customers;")
+        Path(py.path).unlink()
+
+    editor = threading.Thread(target=edit)
+    editor.start()
+    manager.watch(on_change, debounce_ms=50, timeout=10)
+    editor.join()
+
+    assert kinds == {sql.path: ChangeKind.Modified, py.path: ChangeKind.Removed}
+    assert manager.get_task("get_customers.sql").command == "SELECT id FROM customers;"
+
+
+def test_manager_watch_timeout(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    manager = SubtaskManager(base)
+    calls = []
+
+    started = time.monotonic()
+    manager.watch(calls.append, timeout=0.3)
+
+    assert calls == []
+    assert time.monotonic() - started < 5
+
+
+def _archive_tree(tmp_path: Path, suffix: str, prefix: str = "") -> Path:
+    base = _create_etl_structure(tmp_path / "src")
+    archive = tmp_path / f"tasks{suffix}"
+    files = [p for p in sorted(base.rglob("*")) if p.is_file()]
+    if suffix == ".zip":
+        with zipfile.ZipFile(archive, "w") as zf:
+            for f in files:
+                zf.write(f, prefix + f.relative_to(base).as_posix())
+    else:
+        with tarfile.open(archive, "w:gz") as tf:
+            for f in files:
+                tf.add(f, prefix + f.relative_to(base).as_posix())
+    return archive
+
+
+@pytest.mark.parametrize("suffix", [".zip", ".tar.gz"])
+def test_manager_from_archive(tmp_path: Path, suffix: str):
+    archive = _archive_tree(tmp_path, suffix)
+
+    manager = SubtaskManager(archive)
+
+    assert manager.base_path == str(archive)
+    assert manager.file_paths == [
+        "customers/01_extract/pg/get_customers.sql",
+        "customers/02_transform/duck/sales.py",
+        "shared.yaml",
+    ]
+    task = manager.get_task("get_customers.sql")
+    assert task.path == "customers/01_extract/pg/get_customers.sql"
+    assert task.stage == EtlStage.Extract
+    assert task.entity == "customers"
+    assert task.system_type == SystemType.PostgreSQL
+    assert task.command == "SELECT * FROM customers;"
+    assert manager.get_task("shared.yaml").is_common
+
+
+@pytest.mark.parametrize("suffix", [".zip", ".tar.gz"])
+def test_manager_archive_max_file_size(tmp_path: Path, suffix: str):
+    archive = _archive_tree(tmp_path, suffix)
+
+    manager = SubtaskManager(archive, max_file_size=10, on_error=ErrorPolicy.Collect)
+
+    # entries are refused by the size in the archive header, before they are read
+    assert [s.name for s in manager.subtasks] == ["shared.yaml"]
+    assert [(e.path, e.kind) for e in manager.errors] == [
+        ("customers/01_extract/pg/get_customers.sql", ErrorKind.TooLarge),
+        ("customers/02_transform/duck/sales.py", ErrorKind.TooLarge),
+    ]
+
+
+def test_manager_from_archive_with_prefix(tmp_path: Path):
+    archive = _archive_tree(tmp_path, ".zip", prefix="bundle/etl/")
+
+    manager = SubtaskManager(archive, archive_prefix="bundle/etl")
+
+    assert manager.archive_prefix == "bundle/etl"
+    assert manager.file_paths[0] == "bundle/etl/customers/01_extract/pg/get_customers.sql"
+    task = manager.get_task("sales.py")
+    assert task.stage == EtlStage.Transform
+    assert task.entity == "customers"
+    assert task.command == "print('transform')"
+
+
+def test_manager_archive_refresh(tmp_path: Path):
+    archive = _archive_tree(tmp_path, ".zip")
+    manager = SubtaskManager(archive)
+    assert len(manager.subtasks) == 3
+
+    with zipfile.ZipFile(archive, "a") as zf:
+        zf.writestr("orders/03_load/pg/load.sql", "INSERT INTO orders SELECT 1;")
+
+    changes = manager.refresh()
+    assert changes.added == ["orders/03_load/pg/load.sql"]
+    assert manager.get_task("load.sql").command == "INSERT INTO orders SELECT 1;"
+
+
+def test_manager_archive_prefix_requires_archive(tmp_path: Path):
+    base = _create_etl_structure(tmp_path)
+    with pytest.raises(ValueError, match="archive_prefix requires"):
+        _ = SubtaskManager(base, archive_prefix="etl")
+
+
+def test_manager_from_dict():
+    manager = SubtaskManager.from_dict(
+        {
+            "customers/01_extract/pg/get_customers.sql": "SELECT * FROM customers;",
+            "customers\\02_transform\\duck\\sales.py": b"print('transform')",
+            "shared.yaml": "version: 1",
+            "notes.txt": "not a task",
+            "../outside.sql": "SELECT 1;",
+        }
+    )
+
+    assert manager.base_path == ""
+    assert manager.file_paths == [
+        "customers/01_extract/pg/get_customers.sql",
+        "customers/02_transform/duck/sales.py",
+        "shared.yaml",
+    ]
+    assert [e.path for e in manager.scan_errors] == ["../outside.sql"]
+    task = manager.get_task("get_customers.sql")
+    assert task.stage == EtlStage.Extract
+    assert task.system_type == SystemType.PostgreSQL
+    assert task.command == "SELECT * FROM customers;"
+    assert manager.get_task("sales.py").command == "print('transform')"
+    assert [t.name for t in manager.get_tasks(etl_stage=EtlStage.Transform)] == [
+        "sales.py",
+        "shared.yaml",
+    ]
+    assert not manager.refresh()
+
+
+def test_manager_from_dict_filters_and_errors():
+    files = {
+        "customers/01_extract/pg/a.sql": "SELECT 1;",
+        "customers/deprecated/pg/old.sql": "SELECT 2;",
+        "customers/01_extract/pg/deep/nested/bad.sql": "SELECT 3;",
+        "customers/01_extract/pg/latin.sql": "SELECT 'café';".encode("latin-1"),
+    }
+
+    manager = SubtaskManager.from_dict(
+        files, exclude=["**/deprecated/**"], on_error=ErrorPolicy.Collect
+    )
+    manager.load_all()
+
+    assert [s.name for s in manager.subtasks] == ["a.sql"]
+    kinds = {e.path: e.kind for e in manager.errors}
+    assert kinds == {
+        "customers/01_extract/pg/deep/nested/bad.sql": ErrorKind.FolderStructure,
+        "customers/01_extract/pg/latin.sql": ErrorKind.Encoding,
+    }
+    with pytest.raises(ValueError, match="no files on disk"):
+        manager.watch(lambda events: False, timeout=0)
+    with pytest.raises(ValueError, match="must be str or bytes"):
+        _ = SubtaskManager.from_dict({"a.sql": 1})
+
+
+class _DictSource:
+    """Task source backed by a dict, counting reads"""
+
+    def __init__(self, files: dict[str, str], root: str = ""):
+        self.files = files
+        self.root = root
+        self.reads: list[str] = []
+
+    def list(self):
+        return iter(self.files)
+
+    def read(self, path: str):
+        self.reads.append(path)
+        if path not in self.files:
+            raise KeyError(path)
+        return self.files[path]
+
+
+def test_manager_from_source():
+    source = _DictSource(
+        {
+            "etl/customers/01_extract/pg/get_customers.sql": "SELECT 1;",
+            "etl/customers/02_transform/duck/sales.py": "print('transform')",
+            "other/ignored.sql": "SELECT 2;",
+        },
+        root="etl",
+    )
+
+    manager = SubtaskManager.from_source(source)
+
+    assert manager.file_paths == [
+        "etl/customers/01_extract/pg/get_customers.sql",
+        "etl/customers/02_transform/duck/sales.py",
+    ]
+    task = manager.get_task("get_customers.sql")
+    assert task.entity == "customers"
+    assert task.stage == EtlStage.Extract
+    # without stat() the content is read for change detection only
+    assert not task.is_loaded
+    assert task.command == "SELECT 1;"
+
+    source.files["etl/customers/01_extract/pg/get_customers.sql"] = "SELECT 10;"
+    source.files["etl/customers/03_load/pg/load.sql"] = "INSERT 1;"
+    del source.files["etl/customers/02_transform/duck/sales.py"]
+    changes = manager.refresh()
+    assert changes.added == ["etl/customers/03_load/pg/load.sql"]
+    assert changes.removed == ["etl/customers/02_transform/duck/sales.py"]
+    assert changes.modified == ["etl/customers/01_extract/pg/get_customers.sql"]
+    assert manager.get_task("get_customers.sql").command == "SELECT 10;"
+
+
+def test_manager_from_source_errors():
+    class StatSource(_DictSource):
+        def stat(self, path: str):
+            return (len(self.files[path]), 1.0)
+
+    source = StatSource({"customers/01_extract/pg/a.sql": "SELECT 1;"})
+    manager = SubtaskManager.from_source(source, on_error=ErrorPolicy.Collect)
+    _ = manager.subtasks
+    assert source.reads == []  # stat() replaces reading for fingerprints
+
+    del source.files["customers/01_extract/pg/a.sql"]
+    manager.load_all()
+    assert manager.subtasks == []
+    assert [e.kind for e in manager.errors] == [ErrorKind.Io]
+
+    with pytest.raises(ValueError, match="no files on disk"):
+        manager.watch(lambda events: False, timeout=0)
+    with pytest.raises(ValueError, match="callable 'list'"):
+        _ = SubtaskManager.from_source(object())
+
+
+def test_manager_constructors_share_options(tmp_path: Path):
+    files = {
+        "customers/01_extract/pg/a.sql": "SELECT 1;",
+        "customers/01_extract/pg/b.py": "x = 1",
+    }
+    for rel, content in files.items():
+        path = tmp_path / rel
+        path.parent.mkdir(parents=True, exist_ok=True)
+        _ = path.write_text(content)
+
+    managers = [
+        SubtaskManager(tmp_path, include=["**/*.sql"], read_headers=True),
+        SubtaskManager.from_dict(files, include=["**/*.sql"], read_headers=True),
+        SubtaskManager.from_source(_DictSource(files), include=["**/*.sql"], read_headers=True),
+    ]
+    for manager in managers:
+        assert [t.name for t in manager.subtasks] == ["a.sql"]
+
+    with pytest.raises(TypeError, match="unexpected keyword argument 'lazzy'"):
+        _ = SubtaskManager(tmp_path, lazzy=False)
+    with pytest.raises(TypeError, match="from_dict\\(\\) got an unexpected keyword"):
+        _ = SubtaskManager.from_dict(files, cache_path=tmp_path / "cache.json")
+    with pytest.raises(TypeError, match="argument 'lazy'"):
+        _ = SubtaskManager.from_source(_DictSource(files), lazy="yes")
+
+
+def test_manager_encodings(tmp_path: Path):
+    pg = tmp_path / "customers" / "01_extract" / "pg"
+    pg.mkdir(parents=True)
+    (pg / "bom.sql").write_bytes(b"\xef\xbb\xbfSELECT 1;\r\n")
+    (pg / "cyrillic.sql").write_bytes("SELECT 'Привет';".encode("cp1251"))
+
+    strict = SubtaskManager(tmp_path, on_error=ErrorPolicy.Collect)
+    strict.load_all()
+    assert [s.name for s in strict.subtasks] == ["bom.sql"]
+    assert strict.get_task("bom.sql").command == "SELECT 1;\r\n"
+    assert strict.get_task("bom.sql").encoding == "utf-8"
+    assert [e.kind for e in strict.errors] == [ErrorKind.Encoding]
+
+    manager = SubtaskManager(
+        tmp_path, fallback_encodings=["cp1251"], normalize_newlines=True
+    )
+    assert manager.fallback_encodings == ["windows-1251"]
+    assert manager.normalize_newlines
+    assert manager.get_task("bom.sql").command == "SELECT 1;\n"
+    task = manager.get_task("cyrillic.sql")
+    assert task.encoding == "windows-1251"
+    assert task.command == "SELECT 'Привет';"
+
+    eager = SubtaskManager(tmp_path, fallback_encodings=["cp1251"], lazy=False)
+    assert eager.get_task("cyrillic.sql").encoding == "windows-1251"
+
+    with pytest.raises(ValueError, match="Unknown encoding 'klingon'"):
+        _ = SubtaskManager(tmp_path, fallback_encodings=["klingon"])
+
+
+def test_manager_file_size_limit_and_binary(tmp_path: Path):
+    pg = tmp_path / "customers" / "01_extract" / "pg"
+    pg.mkdir(parents=True)
+    (pg / "small.sql").write_text("SELECT 1;")
+    (pg / "fixture.json").write_text("[" + "0," * 1000 + "0]")
+    (pg / "dump.sql").write_bytes(b"SELECT\x00\x01\x02")
+
+    manager = SubtaskManager(
+        tmp_path, max_file_size=100, on_error=ErrorPolicy.Collect
+    )
+    assert manager.max_file_size == 100
+    assert manager.detect_binary
+    # oversized files are refused from their scanned size, before any read, and
+    # binary ones from their first bytes, without waiting for load_all
+    assert [s.name for s in manager.subtasks] == ["small.sql"]
+    assert [(e.path, e.kind) for e in manager.errors] == [
+        (str(pg / "dump.sql"), ErrorKind.Binary),
+        (str(pg / "fixture.json"), ErrorKind.TooLarge),
+    ]
+    manager.load_all()
+    assert [s.name for s in manager.subtasks] == ["small.sql"]
+    assert [e.kind for e in manager.errors] == [ErrorKind.Binary, ErrorKind.TooLarge]
+
+    permissive = SubtaskManager(tmp_path, detect_binary=False)
+    assert permissive.get_task("dump.sql").command == "SELECT\x00\x01\x02"
+
+    in_memory = SubtaskManager.from_dict(
+        {"big.sql": "x" * 10}, max_file_size=5, on_error=ErrorPolicy.Collect
+    )
+    in_memory.load_all()
+    assert [e.kind for e in in_memory.errors] == [ErrorKind.TooLarge]
+
+
+def test_manager_index_cache(tmp_path: Path):
+    base = _create_etl_structure(tmp_path / "etl")
+    cache_path = tmp_path / "cache" / "index.json"
+    manager = SubtaskManager(base, cache_path=cache_path)
+    assert manager.cache_path == str(cache_path)
+    assert not cache_path.exists()
+    assert len(manager.subtasks) == 3
+    cache = json.loads(cache_path.read_text())
+    assert sorted(cache["entries"]) == sorted(manager.file_paths)
+    assert "SELECT" not in cache_path.read_text()
+
+    # unchanged files are taken from the cache instead of being classified again
+    sql_path = manager.get_task("get_customers.sql").path
+    cache["entries"][sql_path]["result"]["Ok"]["entity"] = "from_cache"
+    _ = cache_path.write_text(json.dumps(cache))
+    cached = SubtaskManager(base, cache_path=cache_path)
+    task = cached.get_task("get_customers.sql")
+    assert task.entity == "from_cache"
+    assert task.command == "SELECT * FROM customers;"
+
+    # edited files are classified again
+    _ = Path(sql_path).write_text("SELECT id FROM customers;")
+    edited = SubtaskManager(base, cache_path=cache_path)
+    assert edited.get_task("get_customers.sql").entity == "customers"
+
+    # other settings invalidate the whole cache
+    cache = json.loads(cache_path.read_text())
+    cache["entries"][sql_path]["result"]["Ok"]["entity"] = "from_cache"
+    _ = cache_path.write_text(json.dumps(cache))
+    limited = SubtaskManager(base, cache_path=cache_path, max_file_size=1000)
+    assert limited.get_task("get_customers.sql").entity == "customers"
+
+    # a corrupt cache is rebuilt, and refresh keeps it current
+    _ = cache_path.write_text("{not json")
+    manager = SubtaskManager(base, cache_path=cache_path)
+    assert len(manager.subtasks) == 3
+    Path(manager.get_task("sales.py").path).unlink()
+    assert manager.refresh().removed
+    assert len(json.loads(cache_path.read_text())["entries"]) == 2
+
+
+def test_manager_layout():
+    files = {
+        "pg/orders/02_transform/b.sql": "SELECT 2;",
+        "pg/orders/01_extract/a.sql": "SELECT 1;",
+        "orders/01_extract/pg/c.sql": "SELECT 3;",
+    }
+    manager = SubtaskManager.from_dict(
+        files,
+        layout="{system}/{entity}/{stage}",
+        sort_by=SortOrder.Stage,
+        on_error=ErrorPolicy.Collect,
+    )
+    assert manager.classifier.layout == "{system}/{entity}/{stage}"
+    assert [s.name for s in manager.subtasks] == ["a.sql", "b.sql"]
+    assert [(s.system_type, s.entity) for s in manager.subtasks] == [
+        (SystemType.PostgreSQL, "orders"),
+        (SystemType.PostgreSQL, "orders"),
+    ]
+    assert [(e.path, e.kind) for e in manager.errors] == [
+        ("orders/01_extract/pg/c.sql", ErrorKind.FolderStructure)
+    ]
+
+    with pytest.raises(ValueError, match="Invalid layout"):
+        _ = SubtaskManager.from_dict(files, layout="{entity}/{entity}")
+
+
+def test_manager_entity_prefix():
+    files = {
+        "finance/billing/invoices/01_extract/pg/a.sql": "SELECT 1;",
+        "finance/billing/refunds/01_extract/pg/b.sql": "SELECT 2;",
+        "finance/ledger/02_transform/c.sql": "SELECT 3;",
+        "financial/01_extract/d.sql": "SELECT 4;",
+        "shared.sql": "SELECT 5;",
+    }
+    manager = SubtaskManager.from_dict(files, max_entity_depth=None)
+
+    def names(**filters):
+        return [s.name for s in manager.get_tasks(include_common=False, **filters)]
+
+    assert names(entity_prefix="finance") == ["a.sql", "b.sql", "c.sql"]
+    assert names(entity_prefix="finance/billing") == ["a.sql", "b.sql"]
+    assert names(entity="finance/billing/invoices") == ["a.sql"]
+    assert names(entity_prefix="finance", etl_stage=EtlStage.Transform) == ["c.sql"]
+    assert manager.get_task("c.sql").entity_path == ["finance", "ledger"]
+
+    shallow = SubtaskManager.from_dict(files, on_error=ErrorPolicy.Collect)
+    assert [s.name for s in shallow.subtasks] == ["d.sql", "shared.sql"]
+    assert [e.kind for e in shallow.errors] == [ErrorKind.FolderStructure] * 3
+
+
+def test_manager_aliases():
+    files = {
+        "orders/10_stg/gp/a.sql": "SELECT 1;",
+        "orders/20_dm/trino/b.sql": "SELECT 2;",
+    }
+    manager = SubtaskManager.from_dict(
+        files,
+        aliases={
+            "stages": {"10_stg": "extract", "20_dm": "load"},
+            "systems": {"gp": "postgres", "trino": "other"},
+        },
+        sort_by=SortOrder.Stage,
+    )
+    assert [(s.stage, s.system_type) for s in manager.subtasks] == [
+        (EtlStage.Extract, SystemType.PostgreSQL),
+        (EtlStage.Load, SystemType.Other),
+    ]
+    assert manager.get_tasks(etl_stage=EtlStage.Load)[0].name == "b.sql"
+    assert [s.name for s in manager.get_tasks(etl_stage="10_stg")] == ["a.sql"]
+    assert [s.name for s in manager.get_tasks(system_type="gp")] == ["a.sql"]
+    assert [s.name for s in manager.get_tasks(etl_stage="20_DM", system_type="trino")] == [
+        "b.sql"
+    ]
+    assert manager.classifier.aliases.systems == {
+        "gp": SystemType.PostgreSQL,
+        "trino": SystemType.Other,
+    }
+
+
+def test_manager_custom_systems():
+    files = {
+        "orders/extract/gp/a.sql": "SELECT 1;",
+        "orders/load/kafka/b.py": "print(2)",
+        "orders/load/pg/c.sql": "SELECT 3;",
+    }
+    manager = SubtaskManager.from_dict(
+        files,
+        aliases={
+            "custom_systems": {
+                "Greenplum": {"aliases": ["gp"], "attributes": {"dialect": "postgres"}},
+                "Kafka": {},
+            },
+        },
+        sort_by=SortOrder.Stage,
+    )
+    greenplum = CustomSystem("Greenplum", ["gp"], {"dialect": "postgres"})
+    assert [s.system_type for s in manager.subtasks] == [
+        greenplum,
+        CustomSystem("Kafka"),
+        SystemType.PostgreSQL,
+    ]
+    assert manager.subtasks[0].system_type.attributes == {"dialect": "postgres"}
+    assert "system_type=Some(\"Greenplum\")" in repr(manager.subtasks[0])
+
+    # filter by custom system, its name or alias, or a built-in type
+    for system in [greenplum, "greenplum", "GP"]:
+        assert [s.name for s in manager.get_tasks(system_type=system)] == ["a.sql"]
+    assert [s.name for s in manager.get_tasks(system_type="postgres")] == ["c.sql"]
+    assert [s.name for s in manager.get_tasks(system_type=SystemType.PostgreSQL)] == [
+        "c.sql"
+    ]
+
+
+def test_manager_custom_stages():
+    files = {
+        "orders/load/pg/a.sql": "SELECT 1;",
+        "orders/25_validate/pg/b.sql": "SELECT 2;",
+        "orders/transform/pg/c.sql": "SELECT 3;",
+        "orders/publish/pg/d.sql": "SELECT 4;",
+    }
+    manager = SubtaskManager.from_dict(
+        files,
+        aliases={
+            "custom_stages": {
+                "Validate": {"ordinal": 25, "aliases": ["25_validate"]},
+                "Publish": {"ordinal": 55},
+            },
+        },
+        sort_by=SortOrder.Stage,
+    )
+    validate = CustomStage("Validate", 25, ["25_validate"])
+    assert [s.name for s in manager.subtasks] == ["c.sql", "b.sql", "a.sql", "d.sql"]
+    assert [s.stage for s in manager.subtasks] == [
+        EtlStage.Transform,
+        validate,
+        EtlStage.Load,
+        CustomStage("Publish", 55),
+    ]
+    assert "stage=Some(\"Validate\")" in repr(manager.subtasks[1])
+
+    for stage in [validate, "validate", "25_VALIDATE"]:
+        assert [s.name for s in manager.get_tasks(etl_stage=stage)] == ["b.sql"]
+    assert [s.name for s in manager.get_tasks(etl_stage="load")] == ["a.sql"]
+    assert [s.name for s in manager.get_tasks(etl_stage=EtlStage.Transform)] == ["c.sql"]
+
+
+def test_manager_strict_classification():
+    files = {
+        "orders/load/pg/a.sql": "SELECT 1;",
+        "orders/pg/duck/b.sql": "SELECT 2;",
+    }
+    lenient = SubtaskManager.from_dict(files)
+    assert len(lenient.subtasks) == 2
+    warnings = lenient.classification_warnings
+    assert [(w.path, w.kind) for w in warnings] == [("orders/pg/duck/b.sql", ErrorKind.Ambiguous)]
+
+    strict = SubtaskManager.from_dict(
+        files, strict_classification=True, on_error=ErrorPolicy.Collect
+    )
+    assert [s.name for s in strict.subtasks] == ["a.sql"]
+    assert [(e.path, e.kind) for e in strict.errors] == [
+        ("orders/pg/duck/b.sql", ErrorKind.Ambiguous)
+    ]
+    assert strict.classification_warnings == []
+    assert strict.classifier.strict
+
+
+def test_manager_read_headers():
+    files = {
+        "orders/extract/pg/a.sql": "-- @stage: load\n-- @depends_on: b.sql\nSELECT 1;",
+        "orders/extract/pg/b.sql": "SELECT 2;",
+        "orders/extract/pg/c.sql": "-- @system: nope\nSELECT 3;",
+    }
+    ignored = SubtaskManager.from_dict(files, on_error=ErrorPolicy.Collect)
+    assert ignored.read_headers is False
+    assert ignored.classifier.read_headers is False
+    assert [s.stage for s in ignored.subtasks] == [EtlStage.Extract] * 3
+
+    manager = SubtaskManager.from_dict(files, read_headers=True, on_error=ErrorPolicy.Collect)
+    assert manager.read_headers is True
+    assert [(s.name, s.stage) for s in manager.subtasks] == [
+        ("a.sql", EtlStage.Load),
+        ("b.sql", EtlStage.Extract),
+    ]
+    assert manager.subtasks[0].metadata == {"depends_on": "b.sql"}
+    assert [s.name for s in manager.get_tasks(etl_stage=EtlStage.Load)] == ["a.sql"]
+    assert [(e.path, e.kind) for e in manager.errors] == [
+        ("orders/extract/pg/c.sql", ErrorKind.Header)
+    ]
+
+    strict = SubtaskManager.from_dict(
+        files, read_headers=True, strict_classification=True, on_error=ErrorPolicy.Collect
+    )
+    assert [s.name for s in strict.subtasks] == ["b.sql"]
+    assert [e.kind for e in strict.errors] == [ErrorKind.Header, ErrorKind.Header]
+
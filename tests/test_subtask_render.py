@@ -1,3 +1,5 @@
+import hashlib
+import os
 from pathlib import Path
 
 from subtask_manager import RenderedSubtask, Subtask, SubtaskManager
@@ -98,3 +100,24 @@ def test_immutability():
     assert applied2.rendered_command is not None
     assert "123" in applied2.rendered_command
     assert "999" in applied.rendered_command  # First one unchanged
+
+
+def test_file_metadata_and_hashes():
+    """Subtasks carry file size, mtime and content hashes of template and rendering."""
+    sm: SubtaskManager = SubtaskManager(get_default_path())
+    subtask: Subtask = sm.get_task("dollar0.sql")
+    stat = os.stat(subtask.path)
+
+    assert subtask.size_bytes == stat.st_size
+    assert abs(subtask.modified_at - stat.st_mtime) < 1e-3
+    template = Path(subtask.path).read_text()
+    assert subtask.content_hash == hashlib.sha256(template.encode()).hexdigest()
+
+    rendered = subtask.render_with_params({"user_id": "1"})
+    assert rendered.content_hash == hashlib.sha256(rendered.command.encode()).hexdigest()
+    assert rendered.content_hash != subtask.content_hash
+    # rendering keeps the file metadata of the template
+    assert subtask.apply_parameters({"user_id": "1"}).size_bytes == stat.st_size
+
+    assert Subtask("x.sql").content_hash is None
+    assert Subtask("x.sql").size_bytes is None